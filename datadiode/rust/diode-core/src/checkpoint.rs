@@ -0,0 +1,110 @@
+//! Контрольная точка отправки: если машина отправителя или адаптер пропадут посреди большой
+//! передачи, `resume` продолжит её с того же места под тем же идентификатором, а приёмник
+//! допишет продолжение в тот же временный файл.
+
+use std::fs::{rename, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use crate::error::{Error, Result};
+use crate::protocol::{hex, Header};
+
+/// Как часто отправитель сохраняет контрольную точку.
+const CHECKPOINT_INTERVAL: Duration = Duration::from_secs(1);
+/// Записанные в порт кадры могут ещё лежать в буферах драйвера и адаптера, когда тот
+/// пропадает, поэтому продолжение начинается на столько байт раньше сохранённого места.
+pub const RESUME_OVERLAP: u64 = 64 * 1024;
+
+/// Что нужно, чтобы продолжить прерванную передачу.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Checkpoint {
+    /// Абсолютный путь отправляемого файла.
+    pub file: PathBuf,
+    /// Заголовок передачи: тот же идентификатор и те же параметры кодирования.
+    pub header: Header,
+    pub sha256: [u8; 32],
+    /// Копия и номер куска (группы RS-блоков или сегмента RaptorQ), до которого дошла отправка.
+    pub copy: u32,
+    pub next: usize,
+}
+
+impl Checkpoint {
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = std::fs::read_to_string(path).map_err(Error::io("Не удалось прочитать контрольную точку"))?;
+        let invalid = |reason: &str| Error::Policy(format!("Некорректная контрольная точка {}: {}", path.display(), reason));
+        let field = |name: &str| {
+            text.lines()
+                .find_map(|line| line.strip_prefix(name).and_then(|rest| rest.strip_prefix(' ')))
+                .ok_or_else(|| invalid(&format!("нет поля {}", name)))
+        };
+        let header = unhex(field("header")?).ok_or_else(|| invalid("заголовок не в hex"))?;
+        let sha256 = unhex(field("sha256")?).and_then(|digest| digest.try_into().ok()).ok_or_else(|| invalid("неверная SHA-256"))?;
+        Ok(Checkpoint {
+            file: PathBuf::from(field("file")?),
+            header: Header::decode(&header)?,
+            sha256,
+            copy: field("copy")?.parse().map_err(|_| invalid("неверный номер копии"))?,
+            next: field("next")?.parse().map_err(|_| invalid("неверный номер куска"))?,
+        })
+    }
+
+    /// Сохраняет точку через временный файл, чтобы сбой питания не оставил её наполовину записанной.
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let text = format!(
+            "# Контрольная точка отправки, продолжить: sender --checkpoint {} resume\nfile {}\nheader {}\nsha256 {}\ncopy {}\nnext {}\n",
+            path.display(),
+            self.file.display(),
+            hex(&self.header.encode()),
+            hex(&self.sha256),
+            self.copy,
+            self.next
+        );
+        let mut tmp = path.as_os_str().to_owned();
+        tmp.push(".tmp");
+        let mut file = File::create(&tmp).map_err(Error::io("Ошибка записи контрольной точки"))?;
+        file.write_all(text.as_bytes()).map_err(Error::io("Ошибка записи контрольной точки"))?;
+        file.sync_all().map_err(Error::io("Ошибка записи контрольной точки"))?;
+        rename(&tmp, path).map_err(Error::io("Ошибка записи контрольной точки"))
+    }
+}
+
+fn unhex(text: &str) -> Option<Vec<u8>> {
+    if !text.is_ascii() || !text.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..text.len()).step_by(2).map(|i| u8::from_str_radix(&text[i..i + 2], 16).ok()).collect()
+}
+
+/// Сохраняет контрольную точку по ходу отправки не чаще раза в `CHECKPOINT_INTERVAL`.
+pub(crate) struct Tracker {
+    path: PathBuf,
+    pub(crate) checkpoint: Checkpoint,
+    saved: Instant,
+}
+
+impl Tracker {
+    pub(crate) fn new(path: &Path, checkpoint: Checkpoint) -> Result<Self> {
+        checkpoint.save(path)?;
+        Ok(Tracker { path: path.to_path_buf(), checkpoint, saved: Instant::now() })
+    }
+
+    /// Отмечает, что кадры копии `copy` до куска `next` записаны в `out`. Перед сохранением
+    /// точки буфер сбрасывается в транспорт.
+    pub(crate) fn progress(&mut self, out: &mut impl Write, copy: u32, next: usize) -> Result<()> {
+        if self.saved.elapsed() < CHECKPOINT_INTERVAL {
+            return Ok(());
+        }
+        out.flush().map_err(Error::io("Ошибка отправки данных"))?;
+        self.checkpoint.copy = copy;
+        self.checkpoint.next = next;
+        self.checkpoint.save(&self.path)?;
+        self.saved = Instant::now();
+        Ok(())
+    }
+
+    /// Передача закончена: продолжать нечего.
+    pub(crate) fn finish(self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
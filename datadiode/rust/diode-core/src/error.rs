@@ -0,0 +1,53 @@
+use std::io;
+use thiserror::Error;
+
+/// Ошибки отправки и приёма. Вызывающий код различает их по варианту, а не по тексту сообщения.
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("{context}: {source}")]
+    Io {
+        context: &'static str,
+        #[source]
+        source: io::Error,
+    },
+    /// Порт не принял или не отдал данные за отведённое время.
+    #[error("{context}: таймаут")]
+    Timeout {
+        context: &'static str,
+        #[source]
+        source: io::Error,
+    },
+    /// Источник данных закончился: файл прочитан до конца или закрыт канал.
+    #[error("Источник данных закрыт")]
+    Closed,
+    #[error("Ошибка открытия порта {port}: {source}")]
+    PortOpen {
+        port: String,
+        #[source]
+        source: serialport::Error,
+    },
+    /// Повреждение, которое не смог исправить код: заголовок, трейлер или части файла.
+    #[error("{0}")]
+    Decode(String),
+    /// Файл не прошёл проверку: контрольная сумма не совпала или он изменился во время отправки.
+    #[error("{0}")]
+    Integrity(String),
+    /// Заголовок цел, но описывает передачу, которую этот приёмник не понимает.
+    #[error("{0}")]
+    Protocol(String),
+    /// Параметры передачи или файл не подходят для отправки.
+    #[error("{0}")]
+    Policy(String),
+}
+
+impl Error {
+    /// Оборачивает ошибку ввода-вывода; истёкший таймаут становится `Error::Timeout`.
+    pub fn io(context: &'static str) -> impl FnOnce(io::Error) -> Error {
+        move |source| match source.kind() {
+            io::ErrorKind::TimedOut => Error::Timeout { context, source },
+            _ => Error::Io { context, source },
+        }
+    }
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
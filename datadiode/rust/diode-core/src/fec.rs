@@ -0,0 +1,90 @@
+//! Коды коррекции ошибок: блоки Reed-Solomon с перемежением и сегменты RaptorQ.
+
+use raptorq::{ObjectTransmissionInformation, SourceBlockEncoder};
+use reed_solomon::Encoder;
+
+use crate::error::{Error, Result};
+
+/// Длина кодового слова Reed-Solomon над GF(256): данные + проверочные байты.
+pub const RS_CODEWORD_LEN: usize = 255;
+
+/// Сколько байтов данных помещается в один RS-блок при `rs_bytes` проверочных байтах.
+pub fn rs_block_len(rs_bytes: u8) -> Result<usize> {
+    if rs_bytes as usize >= RS_CODEWORD_LEN {
+        return Err(Error::Policy(format!(
+            "Число проверочных байтов RS должно быть не больше {}",
+            RS_CODEWORD_LEN - 1
+        )));
+    }
+    Ok(RS_CODEWORD_LEN - rs_bytes as usize)
+}
+
+/// Кодирует данные поблочно: каждый кусок по `rs_block_len` байт становится отдельным кодовым словом.
+pub fn encode_blocks(data: &[u8], rs_bytes: u8) -> Result<Vec<Vec<u8>>> {
+    let block_len = rs_block_len(rs_bytes)?;
+    let encoder = Encoder::new(rs_bytes as usize);
+    Ok(data.chunks(block_len).map(|chunk| encoder.encode(chunk).to_vec()).collect())
+}
+
+/// Перемежает группу кодовых слов: соседние байты в линии принадлежат разным словам,
+/// и пакет ошибок длиной `b` портит в каждом слове не больше `ceil(b / глубина)` байт.
+pub fn interleave(codewords: &[Vec<u8>]) -> Vec<u8> {
+    let longest = codewords.iter().map(Vec::len).max().unwrap_or(0);
+    let mut out = Vec::with_capacity(codewords.iter().map(Vec::len).sum());
+    for pos in 0..longest {
+        for codeword in codewords {
+            if let Some(&byte) = codeword.get(pos) {
+                out.push(byte);
+            }
+        }
+    }
+    out
+}
+
+/// Разбирает перемеженную группу обратно на кодовые слова длиной `lens`.
+pub fn deinterleave<T: Copy>(payload: &[T], lens: &[usize]) -> Vec<Vec<T>> {
+    let mut codewords: Vec<Vec<T>> = lens.iter().map(|&len| Vec::with_capacity(len)).collect();
+    let longest = lens.iter().copied().max().unwrap_or(0);
+    let mut bytes = payload.iter();
+    for pos in 0..longest {
+        for (codeword, &len) in codewords.iter_mut().zip(lens) {
+            if pos < len
+                && let Some(&byte) = bytes.next()
+            {
+                codeword.push(byte);
+            }
+        }
+    }
+    codewords
+}
+
+/// Размер символа RaptorQ: пакет с номером и CRC умещается в кадр того же размера, что и RS-блок.
+pub const RAPTORQ_SYMBOL_SIZE: u16 = 240;
+/// Символов в сегменте: декодер держит в памяти только сегмент, а не весь файл.
+pub const RAPTORQ_SEGMENT_SYMBOLS: u16 = 1024;
+pub const RAPTORQ_SEGMENT_LEN: usize = RAPTORQ_SYMBOL_SIZE as usize * RAPTORQ_SEGMENT_SYMBOLS as usize;
+/// Наибольшее число исходных символов в блоке RaptorQ по RFC 6330.
+pub const RAPTORQ_MAX_SYMBOLS: u16 = 56403;
+
+/// Кодирует сегмент файла в пакеты RaptorQ: номер пакета (4), символ, CRC-32 (4).
+pub fn encode_segment(segment: &[u8], symbol_size: u16, repair_percent: u8) -> Vec<Vec<u8>> {
+    let config = ObjectTransmissionInformation::new(0, symbol_size, 0, 1, 1);
+    let symbol_size = symbol_size as usize;
+    let mut padded = segment.to_vec();
+    padded.resize(segment.len().div_ceil(symbol_size) * symbol_size, 0);
+    let encoder = SourceBlockEncoder::new2(0, &config, &padded);
+    let symbols = (padded.len() / symbol_size) as u32;
+    let repair = (symbols * repair_percent as u32).div_ceil(100);
+
+    let mut packets = encoder.source_packets();
+    packets.extend(encoder.repair_packets(0, repair));
+    packets
+        .iter()
+        .map(|packet| {
+            let mut bytes = packet.serialize();
+            let crc = crc32fast::hash(&bytes);
+            bytes.extend_from_slice(&crc.to_be_bytes());
+            bytes
+        })
+        .collect()
+}
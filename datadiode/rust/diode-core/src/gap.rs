@@ -0,0 +1,221 @@
+//! Отчёт о пропусках: короткий код, который оператор переносит с приёмника на отправителя
+//! вручную — обратного канала у диода нет. По коду отправитель шлёт заново только
+//! невосстановленные части, а приёмник дописывает их в сохранённый частичный файл.
+
+use std::ops::RangeInclusive;
+
+use crate::error::{Error, Result};
+
+/// Код начинается с метки, чтобы его нельзя было спутать с чем-то другим.
+const GAP_PREFIX: &str = "DDGAP";
+const GAP_VERSION: u8 = 2;
+/// В первой версии вид кода коррекции не хранился: ноль проверочных байтов означал RaptorQ.
+const GAP_VERSION_1: u8 = 1;
+/// Длина групп символов, разделённых дефисом, чтобы код удобно было переписывать.
+const GAP_GROUP_LEN: usize = 5;
+/// Base32 Крокфорда: без I, L, O и U, которые легко перепутать при переписывании.
+const ALPHABET: &[u8; 32] = b"0123456789ABCDEFGHJKMNPQRSTVWXYZ";
+/// Байтов SHA-256 в коде: хватает, чтобы отправитель не взял другой файл.
+pub const GAP_DIGEST_LEN: usize = 8;
+const FLAG_DIGEST: u8 = 1;
+/// Части — сегменты RaptorQ. RS без проверочных байтов тоже допустим, поэтому ноль в поле
+/// `rs_bytes` сам по себе RaptorQ не означает.
+const FLAG_RAPTORQ: u8 = 2;
+
+/// Что приёмнику не хватает, чтобы закончить передачу.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GapReport {
+    pub transfer_id: u64,
+    pub file_size: u64,
+    /// Проверочных байтов RS, если части — RS-блоки; `None` — сегменты RaptorQ.
+    pub rs_bytes: Option<u8>,
+    /// Начало SHA-256 файла из трейлера; `None`, если трейлер не принят.
+    pub digest: Option<[u8; GAP_DIGEST_LEN]>,
+    /// Невосстановленные части по возрастанию. Пусто — не хватает только трейлера.
+    pub missing: Vec<RangeInclusive<usize>>,
+}
+
+impl GapReport {
+    /// Номера невосстановленных частей.
+    pub fn units(&self) -> impl Iterator<Item = usize> + '_ {
+        self.missing.iter().flat_map(|range| range.clone())
+    }
+
+    pub fn encode(&self) -> String {
+        let mut flags = 0;
+        if self.digest.is_some() {
+            flags |= FLAG_DIGEST;
+        }
+        if self.rs_bytes.is_none() {
+            flags |= FLAG_RAPTORQ;
+        }
+        let mut data = vec![GAP_VERSION, flags, self.rs_bytes.unwrap_or(0)];
+        data.extend_from_slice(&self.transfer_id.to_be_bytes());
+        put_varint(&mut data, self.file_size);
+        if let Some(digest) = &self.digest {
+            data.extend_from_slice(digest);
+        }
+        put_varint(&mut data, self.missing.len() as u64);
+        let mut next = 0;
+        for range in &self.missing {
+            put_varint(&mut data, (range.start() - next) as u64);
+            put_varint(&mut data, (range.end() - range.start()) as u64);
+            next = range.end() + 1;
+        }
+        let crc = crc32fast::hash(&data);
+        data.extend_from_slice(&crc.to_be_bytes());
+
+        let symbols = base32_encode(&data);
+        let groups: Vec<&str> = symbols.as_bytes().chunks(GAP_GROUP_LEN).map(|g| std::str::from_utf8(g).unwrap()).collect();
+        format!("{}-{}", GAP_PREFIX, groups.join("-"))
+    }
+
+    /// Разбирает код, переписанный вручную: регистр, пробелы и дефисы не важны,
+    /// O читается как 0, I и L — как 1.
+    pub fn decode(code: &str) -> Result<Self> {
+        let invalid = |reason: &str| Error::Policy(format!("Некорректный код пропусков: {}", reason));
+        let cleaned: String = code.chars().filter(|c| !c.is_whitespace() && *c != '-').collect::<String>().to_uppercase();
+        let body = cleaned.strip_prefix(GAP_PREFIX).ok_or_else(|| invalid("нет метки DDGAP"))?;
+        let data = base32_decode(body).ok_or_else(|| invalid("недопустимый символ"))?;
+        if data.len() < 4 {
+            return Err(invalid("слишком короткий"));
+        }
+        let (data, crc) = data.split_at(data.len() - 4);
+        if crc32fast::hash(data).to_be_bytes() != crc {
+            return Err(invalid("контрольная сумма не сходится, проверьте, не ошибка ли в символах"));
+        }
+
+        let mut reader = ByteReader(data);
+        let truncated = || invalid("код обрезан");
+        let version = reader.byte().ok_or_else(truncated)?;
+        if version != GAP_VERSION && version != GAP_VERSION_1 {
+            return Err(invalid("неизвестная версия"));
+        }
+        let flags = reader.byte().ok_or_else(truncated)?;
+        let rs_bytes = Some(reader.byte().ok_or_else(truncated)?);
+        let rs_bytes = match version {
+            GAP_VERSION_1 => rs_bytes.filter(|&rs| rs > 0),
+            _ => rs_bytes.filter(|_| flags & FLAG_RAPTORQ == 0),
+        };
+        let transfer_id = u64::from_be_bytes(reader.take(8).ok_or_else(truncated)?.try_into().unwrap());
+        let file_size = reader.varint().ok_or_else(truncated)?;
+        let digest = match flags & FLAG_DIGEST {
+            0 => None,
+            _ => Some(reader.take(GAP_DIGEST_LEN).ok_or_else(truncated)?.try_into().unwrap()),
+        };
+        let count = reader.varint().ok_or_else(truncated)?;
+        let mut missing = Vec::new();
+        let mut next = 0usize;
+        for _ in 0..count {
+            let start = usize::try_from(reader.varint().ok_or_else(truncated)?)
+                .ok()
+                .and_then(|gap| next.checked_add(gap))
+                .ok_or_else(|| invalid("номер части вне диапазона"))?;
+            let end = usize::try_from(reader.varint().ok_or_else(truncated)?)
+                .ok()
+                .and_then(|len| start.checked_add(len))
+                .ok_or_else(|| invalid("номер части вне диапазона"))?;
+            missing.push(start..=end);
+            next = end.saturating_add(1);
+        }
+        if !reader.0.is_empty() {
+            return Err(invalid("лишние данные"));
+        }
+        Ok(GapReport { transfer_id, file_size, rs_bytes, digest, missing })
+    }
+
+    /// Код в виде QR-кода SVG, чтобы перенести его фотографией экрана.
+    pub fn qr_svg(&self) -> Result<String> {
+        let code = qrcode::QrCode::new(self.encode().as_bytes())
+            .map_err(|e| Error::Policy(format!("Код пропусков не помещается в QR-код: {}", e)))?;
+        Ok(code.render::<qrcode::render::svg::Color>().min_dimensions(256, 256).build())
+    }
+}
+
+/// Собирает подряд идущие номера в диапазоны.
+pub fn unit_ranges(units: impl IntoIterator<Item = usize>) -> Vec<RangeInclusive<usize>> {
+    let mut ranges: Vec<RangeInclusive<usize>> = Vec::new();
+    for unit in units {
+        match ranges.last_mut() {
+            Some(last) if *last.end() + 1 == unit => *last = *last.start()..=unit,
+            _ => ranges.push(unit..=unit),
+        }
+    }
+    ranges
+}
+
+pub(crate) fn put_varint(out: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        out.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+/// Читает поля из среза байтов; `None` — данные кончились раньше.
+pub(crate) struct ByteReader<'a>(pub(crate) &'a [u8]);
+
+impl<'a> ByteReader<'a> {
+    pub(crate) fn take(&mut self, n: usize) -> Option<&'a [u8]> {
+        if self.0.len() < n {
+            return None;
+        }
+        let (head, tail) = self.0.split_at(n);
+        self.0 = tail;
+        Some(head)
+    }
+
+    pub(crate) fn byte(&mut self) -> Option<u8> {
+        self.take(1).map(|b| b[0])
+    }
+
+    pub(crate) fn varint(&mut self) -> Option<u64> {
+        let mut value = 0u64;
+        for shift in (0..64).step_by(7) {
+            let byte = self.byte()?;
+            value |= ((byte & 0x7F) as u64).checked_shl(shift)?;
+            if byte & 0x80 == 0 {
+                return Some(value);
+            }
+        }
+        None
+    }
+}
+
+fn base32_encode(data: &[u8]) -> String {
+    let mut out = String::new();
+    let (mut acc, mut bits) = (0u32, 0);
+    for &byte in data {
+        acc = (acc << 8) | byte as u32;
+        bits += 8;
+        while bits >= 5 {
+            bits -= 5;
+            out.push(ALPHABET[(acc >> bits) as usize & 31] as char);
+        }
+    }
+    if bits > 0 {
+        out.push(ALPHABET[(acc << (5 - bits)) as usize & 31] as char);
+    }
+    out
+}
+
+/// Хвостовые биты последнего символа, не составляющие байт, отбрасываются.
+fn base32_decode(text: &str) -> Option<Vec<u8>> {
+    let mut out = Vec::new();
+    let (mut acc, mut bits) = (0u32, 0);
+    for c in text.chars() {
+        let c = match c {
+            'O' => '0',
+            'I' | 'L' => '1',
+            c => c,
+        };
+        let value = ALPHABET.iter().position(|&a| a as char == c)? as u32;
+        acc = (acc << 5) | value;
+        bits += 5;
+        if bits >= 8 {
+            bits -= 8;
+            out.push((acc >> bits) as u8);
+        }
+    }
+    Some(out)
+}
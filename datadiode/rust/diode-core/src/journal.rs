@@ -0,0 +1,140 @@
+//! Журнал приёма: заголовок передачи, карта принятых частей и имена файлов рядом с временным
+//! файлом `*.part`. Если приёмник перезапустится посреди передачи, он продолжит её по журналу,
+//! как только придёт её заголовок — повторённый внутри копии или в начале следующей копии.
+
+use std::fs::{rename, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::error::{Error, Result};
+use crate::gap::{put_varint, ByteReader};
+use crate::protocol::Header;
+use crate::receive::{with_added_extension, BlockMap};
+
+const JOURNAL_MAGIC: [u8; 4] = *b"DDJR";
+const JOURNAL_VERSION: u8 = 1;
+/// Как часто приёмник обновляет журнал принимаемой передачи.
+pub(crate) const JOURNAL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Состояние принимаемой передачи, которого хватает, чтобы продолжить её после перезапуска.
+pub(crate) struct Journal {
+    pub(crate) header: Header,
+    pub(crate) trailer: Option<[u8; 32]>,
+    /// Имена окончательного и частичного файлов в выходной директории.
+    pub(crate) output_name: String,
+    pub(crate) partial_name: String,
+    pub(crate) received: BlockMap,
+    /// Номера RS-блоков, принятых с исправлениями, число исправленных ошибок и стираний.
+    pub(crate) corrected: Vec<(usize, usize, usize)>,
+}
+
+/// Журнал рядом с временным файлом `part`.
+pub(crate) fn journal_path(part: &Path) -> PathBuf {
+    with_added_extension(part, "journal")
+}
+
+impl Journal {
+    fn encode(&self) -> Vec<u8> {
+        let mut data = JOURNAL_MAGIC.to_vec();
+        data.push(JOURNAL_VERSION);
+        let header = self.header.encode();
+        put_varint(&mut data, header.len() as u64);
+        data.extend_from_slice(&header);
+        match &self.trailer {
+            Some(digest) => {
+                data.push(1);
+                data.extend_from_slice(digest);
+            }
+            None => data.push(0),
+        }
+        for name in [&self.output_name, &self.partial_name] {
+            put_varint(&mut data, name.len() as u64);
+            data.extend_from_slice(name.as_bytes());
+        }
+        put_varint(&mut data, self.corrected.len() as u64);
+        for &(index, errors, erasures) in &self.corrected {
+            for value in [index, errors, erasures] {
+                put_varint(&mut data, value as u64);
+            }
+        }
+        data.extend_from_slice(&self.received.to_bytes());
+        let crc = crc32fast::hash(&data);
+        data.extend_from_slice(&crc.to_be_bytes());
+        data
+    }
+
+    fn decode(data: &[u8]) -> Option<Self> {
+        let (data, crc) = data.split_at_checked(data.len().checked_sub(4)?)?;
+        if crc32fast::hash(data).to_be_bytes() != crc {
+            return None;
+        }
+        let mut reader = ByteReader(data);
+        if reader.take(4)? != JOURNAL_MAGIC || reader.byte()? != JOURNAL_VERSION {
+            return None;
+        }
+        let len = reader.varint()? as usize;
+        let header = Header::decode(reader.take(len)?).ok()?;
+        let trailer = match reader.byte()? {
+            0 => None,
+            _ => Some(reader.take(32)?.try_into().ok()?),
+        };
+        let mut name = || {
+            let len = reader.varint()? as usize;
+            String::from_utf8(reader.take(len)?.to_vec()).ok()
+        };
+        let (output_name, partial_name) = (name()?, name()?);
+        let count = reader.varint()? as usize;
+        let mut corrected = Vec::new();
+        for _ in 0..count {
+            corrected.push((reader.varint()? as usize, reader.varint()? as usize, reader.varint()? as usize));
+        }
+        let received = BlockMap::from_bytes(header.unit_count(), reader.0)?;
+        // Исправления бывают только у принятых блоков и не сверх возможностей кода: иначе
+        // журнал записан не этим приёмником, и статистика по нему была бы бессмысленной.
+        let rs_bytes = header.rs_bytes as usize;
+        let valid = |&(index, errors, erasures): &(usize, usize, usize)| {
+            index < header.unit_count()
+                && received.get(index)
+                && (errors, erasures) != (0, 0)
+                && errors <= rs_bytes / 2
+                && erasures <= rs_bytes - 2 * errors
+        };
+        if !corrected.iter().all(valid) {
+            return None;
+        }
+        Some(Journal { header, trailer, output_name, partial_name, received, corrected })
+    }
+
+    /// Записывает журнал через временный файл, чтобы сбой не оставил его наполовину записанным.
+    pub(crate) fn save(&self, path: &Path) -> Result<()> {
+        let mut tmp = path.as_os_str().to_owned();
+        tmp.push(".tmp");
+        let mut file = File::create(&tmp).map_err(Error::io("Ошибка записи журнала приёма"))?;
+        file.write_all(&self.encode()).map_err(Error::io("Ошибка записи журнала приёма"))?;
+        file.sync_all().map_err(Error::io("Ошибка записи журнала приёма"))?;
+        rename(&tmp, path).map_err(Error::io("Ошибка записи журнала приёма"))
+    }
+
+    /// Ищет в `output_dir` журнал передачи с заголовком `header` и возвращает его вместе
+    /// с путём временного файла.
+    pub(crate) fn find(output_dir: &str, header: &Header) -> Option<(PathBuf, Journal)> {
+        for entry in std::fs::read_dir(output_dir).ok()?.flatten() {
+            let path = entry.path();
+            let Some(part_name) = path.file_name().and_then(|name| name.to_str()).and_then(|name| name.strip_suffix(".journal"))
+            else {
+                continue;
+            };
+            if !part_name.ends_with(".part") {
+                continue;
+            }
+            let Ok(data) = std::fs::read(&path) else { continue };
+            match Journal::decode(&data) {
+                Some(journal) if journal.header == *header => return Some((path.with_file_name(part_name), journal)),
+                Some(_) => {}
+                None => eprintln!("Журнал приёма {} повреждён, пропускаем", path.display()),
+            }
+        }
+        None
+    }
+}
@@ -0,0 +1,32 @@
+//! Общая часть передатчика и приёмника датадиода: формат потока, коды коррекции ошибок,
+//! транспорты (последовательный порт, файл, stdin/stdout, UDP, сервер портов RFC 2217, канал в памяти), отправка и приём файлов,
+//! проверка линии, планирование передачи, повторная отправка пропусков и продолжение прерванной отправки.
+//! Диагностика пишется в stderr: stdout может быть транспортом.
+
+pub mod checkpoint;
+pub mod error;
+pub mod fec;
+pub mod gap;
+mod journal;
+pub mod plan;
+pub mod port;
+pub mod protocol;
+pub mod receive;
+pub mod rfc2217;
+pub mod selftest;
+pub mod send;
+pub mod transport;
+pub mod udp;
+
+pub use checkpoint::Checkpoint;
+pub use error::{Error, Result};
+pub use gap::GapReport;
+pub use plan::{plan, Estimate, Link, Plan};
+pub use port::find_com_port;
+pub use receive::{DamageMap, DamagedRange, DecodeStats, Event, ReceivedFile, Receiver};
+pub use selftest::{receive_selftest, send_selftest, SelfTestPlan, StepReport};
+pub use send::{
+    resend, resend_file, resume, resume_file, send, send_file, FecMode, SendOptions, DEFAULT_REPAIR_PERCENT, HEADER_REPEAT_FRAMES,
+};
+pub use transport::{Endpoint, Rx, Tx};
+pub use udp::UdpConfig;
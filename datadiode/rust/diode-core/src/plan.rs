@@ -0,0 +1,243 @@
+//! Планирование передачи: время, полезная скорость и вероятность неудачи для каждой схемы
+//! коррекции ошибок при заданной линии, и самая быстрая схема, укладывающаяся в цель по надёжности.
+//! Ошибки бит считаются независимыми; пачки ошибок модель не учитывает.
+
+use std::fmt;
+use std::time::Duration;
+
+use crate::error::{Error, Result};
+use crate::fec::{RAPTORQ_SEGMENT_LEN, RAPTORQ_SEGMENT_SYMBOLS, RAPTORQ_SYMBOL_SIZE, RS_CODEWORD_LEN};
+use crate::protocol::{Fec, Header, FRAME_FIELDS_ECC, FRAME_HEADER_LEN, HEADER_ECC, SYNC, SYNC_MAX_BIT_ERRORS};
+use crate::send::{FecMode, SendOptions, HEADER_REPEAT_FRAMES};
+
+/// Длина кодового слова трейлера: SHA-256, CRC-32 и проверочные байты.
+const TRAILER_LEN: usize = 36 + HEADER_ECC;
+/// Пакет RaptorQ в кадре: номер (4), символ и CRC-32 (4).
+const RAPTORQ_PACKET_LEN: usize = 4 + RAPTORQ_SYMBOL_SIZE as usize + 4;
+/// Вероятность, что RaptorQ не декодирует сегмент по K принятым символам; каждый лишний
+/// символ уменьшает её примерно в сто раз (RFC 6330).
+const RAPTORQ_FAILURE_AT_K: f64 = 0.01;
+/// Наибольшее число испытаний: пакеты сегмента RaptorQ при наибольшей избыточности.
+const MAX_BINOMIAL_N: usize = RAPTORQ_SEGMENT_SYMBOLS as usize * (100 + u8::MAX as usize) / 100 + 1;
+
+/// Линия: скорость, бит на байт с учётом старт-, стоп- и битов чётности, вероятность ошибки бита.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Link {
+    pub baud: u32,
+    pub bits_per_byte: u32,
+    pub ber: f64,
+}
+
+impl Link {
+    fn check(&self) -> Result<()> {
+        if self.baud == 0 {
+            return Err(Error::Policy("Скорость не может быть нулевой".to_string()));
+        }
+        if !(8..=12).contains(&self.bits_per_byte) {
+            return Err(Error::Policy(format!("Бит на байт в линии должно быть от 8 до 12: {}", self.bits_per_byte)));
+        }
+        if !(0.0..=0.5).contains(&self.ber) {
+            return Err(Error::Policy(format!("BER должен быть от 0 до 0.5: {}", self.ber)));
+        }
+        Ok(())
+    }
+}
+
+/// Оценка одной схемы.
+#[derive(Debug, Clone)]
+pub struct Estimate {
+    pub options: SendOptions,
+    /// Байтов в линии со всеми копиями, кадрами, заголовками и трейлерами.
+    pub wire_bytes: u64,
+    pub duration: Duration,
+    /// Байтов файла в секунду.
+    pub throughput: f64,
+    /// Вероятность не восстановить RS-блок или сегмент RaptorQ ни из одной копии.
+    pub unit_failure: f64,
+    /// Вероятность не принять файл. Отказы блоков считаются независимыми, поэтому оценка
+    /// не меньше настоящей: блоки одного кадра пропадают вместе.
+    pub file_failure: f64,
+}
+
+impl fmt::Display for Estimate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.options.fec {
+            FecMode::ReedSolomon => write!(f, "RS {}, перемежение {}", self.options.rs_bytes, self.options.interleave)?,
+            FecMode::RaptorQ { repair_percent } => write!(f, "RaptorQ {}%", repair_percent)?,
+        }
+        write!(
+            f,
+            ", копий {}: в линии {} байт, {:.1} с, {:.1} КБ/с, отказ блока {:.1e}, отказ файла {:.1e}",
+            self.options.repeat,
+            self.wire_bytes,
+            self.duration.as_secs_f64(),
+            self.throughput / 1024.0,
+            self.unit_failure,
+            self.file_failure
+        )
+    }
+}
+
+/// Логарифмы факториалов до `n` для биномиальных вероятностей.
+struct LnFactorials(Vec<f64>);
+
+impl LnFactorials {
+    fn new(n: usize) -> Self {
+        let mut table = vec![0.0; n + 1];
+        for k in 1..=n {
+            table[k] = table[k - 1] + (k as f64).ln();
+        }
+        LnFactorials(table)
+    }
+
+    /// P(X = k) для X ~ Bin(n, p), в логарифмах, чтобы не уходить в ноль на длинных кодах.
+    fn pmf(&self, n: usize, k: usize, p: f64) -> f64 {
+        let hits = if k == 0 { 0.0 } else { k as f64 * p.ln() };
+        let misses = if k == n { 0.0 } else { (n - k) as f64 * (-p).ln_1p() };
+        (self.0[n] - self.0[k] - self.0[n - k] + hits + misses).exp()
+    }
+
+    /// P(X > t) для X ~ Bin(n, p): вероятность, что ошибок больше, чем исправляет код.
+    fn tail(&self, n: usize, t: usize, p: f64) -> f64 {
+        (t + 1..=n).map(|k| self.pmf(n, k, p)).sum::<f64>().min(1.0)
+    }
+}
+
+/// Вероятность, что из `n` независимых событий с вероятностью `p` случится хотя бы одно,
+/// без потери точности на малых `p`.
+fn any_of(n: f64, p: f64) -> f64 {
+    if p >= 1.0 { 1.0 } else { -(n * (-p).ln_1p()).exp_m1() }
+}
+
+/// Хотя бы одно из двух независимых событий.
+fn either(a: f64, b: f64) -> f64 {
+    a + b - a * b
+}
+
+/// Вероятность ошибки в байте.
+fn symbol_error(ber: f64) -> f64 {
+    any_of(8.0, ber)
+}
+
+/// Вероятность потерять кадр из-за служебных полей: синхрослово допускает `SYNC_MAX_BIT_ERRORS`
+/// инверсий, поля исправляются своим RS-кодом.
+fn frame_loss(ln: &LnFactorials, ber: f64) -> f64 {
+    let fields_len = FRAME_HEADER_LEN - SYNC.len();
+    either(
+        ln.tail(SYNC.len() * 8, SYNC_MAX_BIT_ERRORS as usize, ber),
+        ln.tail(fields_len, FRAME_FIELDS_ECC / 2, symbol_error(ber)),
+    )
+}
+
+/// Байты заголовков, повторённых внутри копии из `frames` кадров данных.
+fn header_repeats(frames: u64) -> u64 {
+    frames / HEADER_REPEAT_FRAMES as u64 * (FRAME_HEADER_LEN + RS_CODEWORD_LEN) as u64
+}
+
+/// Оценивает передачу файла размером `file_size` со схемой `options` по линии `link`.
+pub fn estimate(file_size: u64, options: &SendOptions, link: &Link) -> Result<Estimate> {
+    link.check()?;
+    let header = Header::new(options, file_size, "", 0)?;
+    let copies = options.repeat as i32;
+    let ln = LnFactorials::new(MAX_BINOMIAL_N);
+    let frame_loss = frame_loss(&ln, link.ber);
+    let symbol_error = symbol_error(link.ber);
+
+    // Без заголовка приёмник не начнёт передачу, без трейлера не проверит файл.
+    let codeword_loss = |len: usize| either(frame_loss, ln.tail(len, HEADER_ECC / 2, symbol_error));
+    let meta_failure = either(codeword_loss(RS_CODEWORD_LEN), codeword_loss(TRAILER_LEN)).powi(copies);
+    let meta_bytes = (2 * FRAME_HEADER_LEN + RS_CODEWORD_LEN + TRAILER_LEN) as u64;
+
+    let (body_bytes, unit_failure, body_failure) = match header.fec {
+        Fec::ReedSolomon => {
+            let blocks = header.block_count() as u64;
+            let frames = header.group_count() as u64;
+            let bytes = frames * FRAME_HEADER_LEN as u64 + file_size + blocks * options.rs_bytes as u64 + header_repeats(frames);
+            let block_loss = either(frame_loss, ln.tail(RS_CODEWORD_LEN, options.rs_bytes as usize / 2, symbol_error));
+            let block_loss = block_loss.powi(copies);
+            (bytes, block_loss, any_of(blocks as f64, block_loss))
+        }
+        Fec::RaptorQ { repair_percent, .. } => {
+            let packet_frame = (FRAME_HEADER_LEN + RAPTORQ_PACKET_LEN) as u64;
+            let packet_loss = either(frame_loss, any_of((RAPTORQ_PACKET_LEN * 8) as f64, link.ber));
+            let full = file_size / RAPTORQ_SEGMENT_LEN as u64;
+            let rest = file_size % RAPTORQ_SEGMENT_LEN as u64;
+            let (mut bytes, mut frames, mut worst, mut body_ok_ln) = (0, 0, 0.0f64, 0.0);
+            for (len, count) in [(RAPTORQ_SEGMENT_LEN as u64, full), (rest, (rest > 0) as u64)] {
+                if count == 0 {
+                    continue;
+                }
+                let symbols = len.div_ceil(RAPTORQ_SYMBOL_SIZE as u64) as usize;
+                let packets = symbols + (symbols * repair_percent as usize).div_ceil(100);
+                // Сегмент не восстановлен, если пакетов меньше K или декодер не справился с принятыми.
+                let failure = (0..=packets)
+                    .map(|received| {
+                        let decode_failure = match received.checked_sub(symbols) {
+                            None => 1.0,
+                            Some(extra) => RAPTORQ_FAILURE_AT_K.powi(extra as i32 + 1),
+                        };
+                        ln.pmf(packets, received, 1.0 - packet_loss) * decode_failure
+                    })
+                    .sum::<f64>()
+                    .min(1.0)
+                    .powi(copies);
+                worst = worst.max(failure);
+                body_ok_ln += count as f64 * (-failure).ln_1p();
+                bytes += count * packets as u64 * packet_frame;
+                frames += count * packets as u64;
+            }
+            (bytes + header_repeats(frames), worst, -body_ok_ln.exp_m1())
+        }
+    };
+
+    let wire_bytes = (meta_bytes + body_bytes) * options.repeat as u64;
+    let seconds = (wire_bytes * link.bits_per_byte as u64) as f64 / link.baud as f64;
+    Ok(Estimate {
+        options: options.clone(),
+        wire_bytes,
+        duration: Duration::from_secs_f64(seconds),
+        throughput: if seconds > 0.0 { file_size as f64 / seconds } else { 0.0 },
+        unit_failure,
+        file_failure: either(meta_failure, body_failure),
+    })
+}
+
+/// Схемы, которые перебирает планировщик: RS с разным числом проверочных байтов и RaptorQ
+/// с разной избыточностью, по одной, две и три копии.
+pub fn candidates(interleave: u8) -> Vec<SendOptions> {
+    let mut options = Vec::new();
+    for repeat in 1..=3 {
+        for rs_bytes in [2, 4, 6, 8, 10, 16, 24, 32, 48, 64] {
+            options.push(SendOptions { rs_bytes, interleave, fec: FecMode::ReedSolomon, repeat, checkpoint: None });
+        }
+        for repair_percent in [5, 10, 20, 30, 50] {
+            options.push(SendOptions { fec: FecMode::RaptorQ { repair_percent }, repeat, ..SendOptions::default() });
+        }
+    }
+    options
+}
+
+/// Оценки всех схем и номер рекомендуемой: самой быстрой, у которой вероятность
+/// не принять файл не больше `target`.
+#[derive(Debug, Clone)]
+pub struct Plan {
+    pub estimates: Vec<Estimate>,
+    pub recommended: Option<usize>,
+}
+
+pub fn plan(file_size: u64, link: &Link, interleave: u8, target: f64) -> Result<Plan> {
+    if !(target > 0.0 && target < 1.0) {
+        return Err(Error::Policy(format!("Цель по надёжности должна быть между 0 и 1: {}", target)));
+    }
+    let estimates = candidates(interleave)
+        .iter()
+        .map(|options| estimate(file_size, options, link))
+        .collect::<Result<Vec<_>>>()?;
+    let recommended = estimates
+        .iter()
+        .enumerate()
+        .filter(|(_, estimate)| estimate.file_failure <= target)
+        .min_by_key(|(_, estimate)| estimate.wire_bytes)
+        .map(|(index, _)| index);
+    Ok(Plan { estimates, recommended })
+}
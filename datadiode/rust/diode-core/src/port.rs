@@ -0,0 +1,195 @@
+//! Последовательный порт: поиск адаптера, открытие и отметки байтов с ошибкой чётности.
+
+use std::fmt;
+use std::io::{self, Read};
+use std::time::Duration;
+
+use serialport::{Parity, SerialPort, SerialPortBuilder};
+
+use crate::error::{Error, Result};
+use crate::transport::Rx;
+
+/// Сколько ждать данных от порта, прежде чем чтение или запись вернут таймаут.
+pub const PORT_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Ищет адаптер USB UART CP2102, из которых собран диод.
+pub fn find_com_port() -> Option<String> {
+    serialport::available_ports()
+        .ok()?
+        .into_iter()
+        .find(|p| {
+            if let serialport::SerialPortType::UsbPort(info) = &p.port_type {
+                info.vid == 0x10C4 && info.pid == 0xEA60 // CP2102
+            } else {
+                false
+            }
+        })
+        .map(|p| p.port_name)
+}
+
+/// Последовательный порт из командной строки: `ИМЯ?parity=none|even|odd`. С чётностью
+/// приёмник узнаёт, какие байты пришли с ошибкой, и передаёт их декодеру RS как стирания:
+/// стирание стоит одного проверочного байта, а не двух, как ошибка в неизвестном месте.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SerialConfig {
+    pub name: String,
+    pub parity: Parity,
+}
+
+impl SerialConfig {
+    pub fn parse(spec: &str) -> Result<Self> {
+        let (name, query) = spec.split_once('?').unwrap_or((spec, ""));
+        let mut config = SerialConfig { name: name.to_string(), parity: Parity::None };
+        for param in query.split('&').filter(|p| !p.is_empty()) {
+            let (key, value) = param.split_once('=').unwrap_or((param, ""));
+            match (key, value) {
+                ("parity", "none") => config.parity = Parity::None,
+                ("parity", "even") => config.parity = Parity::Even,
+                ("parity", "odd") => config.parity = Parity::Odd,
+                ("parity", _) => return Err(Error::Policy(format!("Некорректный параметр порта: {}", param))),
+                _ => return Err(Error::Policy(format!("Неизвестный параметр порта: {}", key))),
+            }
+        }
+        Ok(config)
+    }
+}
+
+impl fmt::Display for SerialConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name)?;
+        match self.parity {
+            Parity::None => Ok(()),
+            Parity::Even => write!(f, "?parity=even"),
+            Parity::Odd => write!(f, "?parity=odd"),
+        }
+    }
+}
+
+fn builder(config: &SerialConfig, baud: u32) -> SerialPortBuilder {
+    eprintln!("Открываем порт: {} с baud {}", config, baud);
+    serialport::new(&config.name, baud).parity(config.parity).timeout(PORT_TIMEOUT)
+}
+
+fn open_error(config: &SerialConfig) -> impl FnOnce(serialport::Error) -> Error + '_ {
+    move |source| Error::PortOpen { port: config.name.clone(), source }
+}
+
+pub fn open(config: &SerialConfig, baud: u32) -> Result<Box<dyn SerialPort>> {
+    let ser = builder(config, baud).open().map_err(open_error(config))?;
+    eprintln!("Порт успешно открыт: {}", config.name);
+    Ok(ser)
+}
+
+/// Открывает порт для приёма. С чётностью на unix драйвер помечает байты с ошибкой
+/// чётности или кадра (PARMRK), и приёмник получает их отметки через `Rx::read_marked`.
+pub fn open_rx(config: &SerialConfig, baud: u32) -> Result<Box<dyn Rx>> {
+    if config.parity == Parity::None {
+        return Ok(Box::new(open(config, baud)?));
+    }
+    #[cfg(unix)]
+    {
+        let ser = builder(config, baud).open_native().map_err(open_error(config))?;
+        mark_parity_errors(&ser).map_err(|e| open_error(config)(e.into()))?;
+        eprintln!("Порт успешно открыт: {}, байты с ошибкой чётности станут стираниями", config.name);
+        Ok(Box::new(ParityMarks::new(Box::new(ser) as Box<dyn SerialPort>)))
+    }
+    #[cfg(not(unix))]
+    {
+        eprintln!("Отметки ошибок чётности на этой платформе недоступны: такие байты исправятся как обычные ошибки");
+        Ok(Box::new(open(config, baud)?))
+    }
+}
+
+/// Включает PARMRK: байт с ошибкой чётности или кадра приходит как `FF 00 X`, байт `FF` — как `FF FF`.
+/// IGNPAR выбросил бы такие байты, ISTRIP срезал бы старший бит у `FF`.
+#[cfg(unix)]
+fn mark_parity_errors(port: &impl std::os::fd::AsRawFd) -> io::Result<()> {
+    let fd = port.as_raw_fd();
+    // SAFETY: дескриптор открыт, пока жив порт, а termios целиком заполняет tcgetattr.
+    unsafe {
+        let mut termios: libc::termios = std::mem::zeroed();
+        if libc::tcgetattr(fd, &mut termios) != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        termios.c_iflag |= libc::PARMRK | libc::INPCK;
+        termios.c_iflag &= !(libc::IGNPAR | libc::ISTRIP);
+        if libc::tcsetattr(fd, libc::TCSANOW, &termios) != 0 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+    Ok(())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum MarkState {
+    Data,
+    /// Принят `FF`: дальше либо второй `FF`, либо `00` и байт с ошибкой.
+    Escape,
+    Marked,
+}
+
+/// Разбирает поток порта в режиме PARMRK: отдаёт байты без пометок, а байты с ошибкой
+/// отмечает в `Rx::read_marked`. Пометка может разорваться между двумя чтениями.
+pub struct ParityMarks<R> {
+    inner: R,
+    raw: Vec<u8>,
+    state: MarkState,
+}
+
+impl<R: Read> ParityMarks<R> {
+    pub fn new(inner: R) -> Self {
+        ParityMarks { inner, raw: Vec::new(), state: MarkState::Data }
+    }
+
+    /// Читает, пока не наберётся хотя бы один байт данных: одни пометки ещё не данные.
+    /// Байтов данных не больше, чем прочитано из порта, поэтому `buf` хватает.
+    fn read_with(&mut self, buf: &mut [u8], mut mark: impl FnMut(usize, bool)) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        self.raw.resize(buf.len(), 0);
+        loop {
+            let n = self.inner.read(&mut self.raw)?;
+            if n == 0 {
+                return Ok(0);
+            }
+            let mut out = 0;
+            for i in 0..n {
+                let byte = self.raw[i];
+                let (next, data) = match (self.state, byte) {
+                    (MarkState::Data, 0xFF) => (MarkState::Escape, None),
+                    (MarkState::Data, _) => (MarkState::Data, Some((byte, false))),
+                    (MarkState::Escape, 0xFF) => (MarkState::Data, Some((0xFF, false))),
+                    (MarkState::Escape, 0x00) => (MarkState::Marked, None),
+                    // Драйвер так не пишет; байту после одиночного `FF` верить нельзя.
+                    (MarkState::Escape, _) | (MarkState::Marked, _) => (MarkState::Data, Some((byte, true))),
+                };
+                self.state = next;
+                if let Some((byte, marked)) = data {
+                    buf[out] = byte;
+                    mark(out, marked);
+                    out += 1;
+                }
+            }
+            if out > 0 {
+                return Ok(out);
+            }
+        }
+    }
+}
+
+impl<R: Read> Read for ParityMarks<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.read_with(buf, |_, _| {})
+    }
+}
+
+impl<R: Rx> Rx for ParityMarks<R> {
+    fn set_baud_rate(&mut self, baud: u32) -> io::Result<()> {
+        self.inner.set_baud_rate(baud)
+    }
+
+    fn read_marked(&mut self, buf: &mut [u8], marks: &mut [bool]) -> io::Result<usize> {
+        self.read_with(buf, |pos, marked| marks[pos] = marked)
+    }
+}
@@ -0,0 +1,416 @@
+//! Формат потока: кадры с синхрословом, заголовок передачи и трейлер с SHA-256 файла.
+
+use std::io;
+
+use reed_solomon::{Decoder, Encoder};
+
+use crate::error::{Error, Result};
+use crate::fec::{RAPTORQ_MAX_SYMBOLS, RAPTORQ_SEGMENT_LEN, RS_CODEWORD_LEN};
+use crate::transport::Rx;
+
+/// Слово синхронизации, с которого начинается каждый кадр (ASM из CCSDS).
+pub const SYNC: [u8; 4] = [0x1A, 0xCF, 0xFC, 0x1D];
+/// Синхрослово узнаётся и с несколькими битовыми ошибками, если за ним следуют верные служебные поля.
+pub(crate) const SYNC_MAX_BIT_ERRORS: u32 = 3;
+/// Служебные поля кадра: тип (1), номер (4), длина полезной нагрузки (2) и CRC-32 этих полей (4).
+const FRAME_FIELDS_LEN: usize = 11;
+/// Служебные поля защищены собственным коротким RS-кодом.
+pub const FRAME_FIELDS_ECC: usize = 4;
+pub const FRAME_HEADER_LEN: usize = SYNC.len() + FRAME_FIELDS_LEN + FRAME_FIELDS_ECC;
+
+pub const FRAME_HEADER: u8 = 1;
+pub const FRAME_DATA: u8 = 2;
+pub const FRAME_TRAILER: u8 = 3;
+/// Кадр проверки линии с псевдослучайной последовательностью, см. `selftest`.
+pub const FRAME_SELFTEST: u8 = 4;
+
+/// Собирает кадр. Полезную нагрузку защищает RS данных, а не кадр.
+pub fn encode_frame(kind: u8, seq: u32, payload: &[u8]) -> Vec<u8> {
+    let len = u16::try_from(payload.len()).expect("полезная нагрузка кадра не больше 65535 байт");
+    let mut fields = [0u8; FRAME_FIELDS_LEN];
+    fields[0] = kind;
+    fields[1..5].copy_from_slice(&seq.to_be_bytes());
+    fields[5..7].copy_from_slice(&len.to_be_bytes());
+    let crc = crc32fast::hash(&fields[..7]);
+    fields[7..].copy_from_slice(&crc.to_be_bytes());
+
+    let mut frame = Vec::with_capacity(FRAME_HEADER_LEN + payload.len());
+    frame.extend_from_slice(&SYNC);
+    frame.extend_from_slice(&Encoder::new(FRAME_FIELDS_ECC).encode(&fields));
+    frame.extend_from_slice(payload);
+    frame
+}
+
+/// Кадр после проверки служебных полей. Полезная нагрузка может быть повреждена или укорочена.
+#[derive(Debug, Clone)]
+pub struct Frame {
+    pub kind: u8,
+    pub seq: u32,
+    pub payload: Vec<u8>,
+    /// Позиции байтов нагрузки, которые линия приняла с ошибкой чётности или кадра.
+    pub erasures: Vec<usize>,
+}
+
+/// Разбирает поток на кадры. Если служебные поля не проходят проверку или кадр оказался короче
+/// заявленного, ищет следующее синхрослово: сбой стоит только того кадра, в котором произошёл.
+pub struct FrameReader<R> {
+    inner: R,
+    buf: Vec<u8>,
+    /// Отметки линии для байтов `buf`, см. `Rx::read_marked`.
+    marks: Vec<bool>,
+    skipped: u64,
+}
+
+impl<R: Rx> FrameReader<R> {
+    pub fn new(inner: R) -> Self {
+        FrameReader { inner, buf: Vec::new(), marks: Vec::new(), skipped: 0 }
+    }
+
+    /// Сколько байтов пропущено при поиске синхрослова с прошлого вызова.
+    pub fn take_skipped(&mut self) -> u64 {
+        std::mem::take(&mut self.skipped)
+    }
+
+    /// Транспорт под читателем: например, чтобы сменить скорость линии.
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.inner
+    }
+
+    fn fill(&mut self, need: usize) -> io::Result<()> {
+        let mut chunk = [0u8; 4096];
+        let mut marks = [false; 4096];
+        while self.buf.len() < need {
+            let n = self.inner.read_marked(&mut chunk, &mut marks)?;
+            if n == 0 {
+                return Err(io::ErrorKind::UnexpectedEof.into());
+            }
+            self.buf.extend_from_slice(&chunk[..n]);
+            self.marks.extend_from_slice(&marks[..n]);
+        }
+        Ok(())
+    }
+
+    fn skip(&mut self, n: usize) {
+        self.buf.drain(..n);
+        self.marks.drain(..n);
+        self.skipped += n as u64;
+    }
+
+    /// Проверяет заголовок кадра с позиции `pos` буфера.
+    fn header_at(&self, pos: usize) -> Option<(u8, u32, usize)> {
+        parse_frame_header(&self.buf[pos..], &self.marks[pos..])
+    }
+
+    pub fn next_frame(&mut self) -> io::Result<Frame> {
+        loop {
+            self.fill(FRAME_HEADER_LEN)?;
+            let Some((kind, seq, len)) = self.header_at(0) else {
+                self.skip(1);
+                continue;
+            };
+            let end = FRAME_HEADER_LEN + len;
+            self.fill(end)?;
+
+            // Заглядываем за конец кадра: если следующий кадр начался раньше,
+            // внутри этого потерялись байты — обрезаем его, чтобы не съесть следующий.
+            let mut frame_end = end;
+            match self.fill(end + FRAME_HEADER_LEN) {
+                Ok(()) if self.header_at(end).is_none() => {
+                    if let Some(pos) = (FRAME_HEADER_LEN..end).find(|&pos| self.header_at(pos).is_some()) {
+                        frame_end = pos;
+                    }
+                }
+                Ok(()) => {}
+                Err(e) if matches!(e.kind(), io::ErrorKind::TimedOut | io::ErrorKind::UnexpectedEof) => {}
+                Err(e) => return Err(e),
+            }
+
+            let payload = self.buf[FRAME_HEADER_LEN..frame_end].to_vec();
+            let erasures = marked_positions(&self.marks[FRAME_HEADER_LEN..frame_end]);
+            self.buf.drain(..frame_end);
+            self.marks.drain(..frame_end);
+            return Ok(Frame { kind, seq, payload, erasures });
+        }
+    }
+}
+
+fn marked_positions(marks: &[bool]) -> Vec<usize> {
+    marks.iter().enumerate().filter(|&(_, &marked)| marked).map(|(pos, _)| pos).collect()
+}
+
+/// Проверяет, начинается ли `buf` с заголовка кадра, и возвращает тип, номер и длину.
+/// Отмеченные линией байты служебных полей исправляются как стирания.
+fn parse_frame_header(buf: &[u8], marks: &[bool]) -> Option<(u8, u32, usize)> {
+    if buf.len() < FRAME_HEADER_LEN {
+        return None;
+    }
+    let sync_errors: u32 = buf.iter().zip(SYNC).map(|(a, b)| (a ^ b).count_ones()).sum();
+    if sync_errors > SYNC_MAX_BIT_ERRORS {
+        return None;
+    }
+    let erasures: Vec<u8> =
+        marked_positions(&marks[SYNC.len()..FRAME_HEADER_LEN]).into_iter().map(|pos| pos as u8).collect();
+    let erase_pos = (!erasures.is_empty() && erasures.len() <= FRAME_FIELDS_ECC).then_some(erasures.as_slice());
+    let fields = Decoder::new(FRAME_FIELDS_ECC)
+        .correct(&buf[SYNC.len()..FRAME_HEADER_LEN], erase_pos)
+        .ok()?;
+    let fields = fields.data();
+    let crc = u32::from_be_bytes(fields[7..].try_into().unwrap());
+    if crc != crc32fast::hash(&fields[..7]) {
+        return None;
+    }
+    let seq = u32::from_be_bytes(fields[1..5].try_into().unwrap());
+    let len = u16::from_be_bytes([fields[5], fields[6]]) as usize;
+    Some((fields[0], seq, len))
+}
+
+/// Сигнатура формата в начале заголовка.
+pub const MAGIC: [u8; 4] = *b"DDIO";
+/// Версия формата заголовка и потока.
+pub const PROTOCOL_VERSION: u8 = 3;
+/// Заголовок — одно кодовое слово RS фиксированной длины с фиксированным числом проверочных байтов,
+/// чтобы приёмник мог прочитать его, ещё не зная параметров передачи.
+pub const HEADER_ECC: usize = 32;
+const HEADER_DATA_LEN: usize = RS_CODEWORD_LEN - HEADER_ECC;
+const HEADER_CRC_OFFSET: usize = HEADER_DATA_LEN - 4;
+const HEADER_NAME_OFFSET: usize = 36;
+/// Максимальная длина имени файла в заголовке, байт.
+pub const MAX_NAME_LEN: usize = HEADER_CRC_OFFSET - HEADER_NAME_OFFSET;
+/// Повторная отправка по отчёту о пропусках (`gap`): в передаче только недостающие части
+/// файла, уже частично принятого под тем же идентификатором.
+pub const FLAG_RESEND: u16 = 1;
+/// Флаги заголовка, известные этой версии.
+const KNOWN_FLAGS: u16 = FLAG_RESEND;
+
+const FEC_REED_SOLOMON: u8 = 0;
+const FEC_RAPTORQ: u8 = 1;
+
+/// Схема коррекции ошибок передачи.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Fec {
+    ReedSolomon,
+    /// Фонтанный код: каждый сегмент из `segment_symbols` символов восстанавливается
+    /// из любого достаточного набора неповреждённых пакетов.
+    RaptorQ { symbol_size: u16, segment_symbols: u16, repair_percent: u8 },
+}
+
+/// Заголовок передачи: всё, что нужно приёмнику для настройки декодера.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Header {
+    pub version: u8,
+    pub rs_bytes: u8,
+    pub block_len: u8,
+    /// Глубина перемежения: сколько RS-блоков перемешаны в одном кадре.
+    pub interleave: u8,
+    pub fec: Fec,
+    pub flags: u16,
+    pub file_size: u64,
+    /// Общий для всех копий одной передачи.
+    pub transfer_id: u64,
+    /// Сколько копий передачи отправляет отправитель.
+    pub copies: u8,
+    /// Длина фрагмента группы RS в кадре данных; 0 — группа целиком в одном кадре.
+    /// Фрагменты нужны транспортам с датаграммами: потерянная датаграмма становится стираниями.
+    pub fragment_len: u16,
+    pub filename: String,
+}
+
+impl Header {
+    /// Кодовое слово заголовка — полезная нагрузка кадра `FRAME_HEADER`.
+    pub fn encode(&self) -> Vec<u8> {
+        let mut data = [0u8; HEADER_DATA_LEN];
+        data[0..4].copy_from_slice(&MAGIC);
+        data[4] = self.version;
+        data[5] = self.rs_bytes;
+        data[6] = self.block_len;
+        data[7..9].copy_from_slice(&self.flags.to_be_bytes());
+        data[9..17].copy_from_slice(&self.file_size.to_be_bytes());
+        data[17] = self.interleave;
+        match self.fec {
+            Fec::ReedSolomon => data[18] = FEC_REED_SOLOMON,
+            Fec::RaptorQ { symbol_size, segment_symbols, repair_percent } => {
+                data[18] = FEC_RAPTORQ;
+                data[19..21].copy_from_slice(&symbol_size.to_be_bytes());
+                data[21..23].copy_from_slice(&segment_symbols.to_be_bytes());
+                data[23] = repair_percent;
+            }
+        }
+        data[24..32].copy_from_slice(&self.transfer_id.to_be_bytes());
+        data[32] = self.copies;
+        data[33..35].copy_from_slice(&self.fragment_len.to_be_bytes());
+        let name = self.filename.as_bytes();
+        data[HEADER_NAME_OFFSET - 1] = name.len() as u8;
+        data[HEADER_NAME_OFFSET..HEADER_NAME_OFFSET + name.len()].copy_from_slice(name);
+        let crc = crc32fast::hash(&data[..HEADER_CRC_OFFSET]);
+        data[HEADER_CRC_OFFSET..].copy_from_slice(&crc.to_be_bytes());
+        Encoder::new(HEADER_ECC).encode(&data).to_vec()
+    }
+
+    /// Исправляет кодовое слово заголовка, проверяет CRC, сигнатуру, версию и согласованность полей.
+    pub fn decode(codeword: &[u8]) -> Result<Self> {
+        if codeword.len() != RS_CODEWORD_LEN {
+            return Err(Error::Decode(format!("Заголовок повреждён: длина {} байт", codeword.len())));
+        }
+        let corrected = Decoder::new(HEADER_ECC)
+            .correct(codeword, None)
+            .map_err(|e| Error::Decode(format!("Заголовок повреждён: {:?}", e)))?;
+        let data = corrected.data();
+
+        let crc = u32::from_be_bytes(data[HEADER_CRC_OFFSET..].try_into().unwrap());
+        if crc != crc32fast::hash(&data[..HEADER_CRC_OFFSET]) {
+            return Err(Error::Decode("Заголовок повреждён: неверная контрольная сумма".to_string()));
+        }
+        if data[0..4] != MAGIC {
+            return Err(Error::Protocol(format!("Неверная сигнатура передачи: {:02x?}", &data[0..4])));
+        }
+
+        let version = data[4];
+        if version != PROTOCOL_VERSION {
+            return Err(Error::Protocol(format!(
+                "Неподдерживаемая версия протокола: {} (поддерживается {})",
+                version, PROTOCOL_VERSION
+            )));
+        }
+
+        let rs_bytes = data[5];
+        let block_len = data[6];
+        if rs_bytes as usize + block_len as usize != RS_CODEWORD_LEN || block_len == 0 {
+            return Err(Error::Protocol(format!("Некорректные параметры RS: {} проверочных байтов, блок {} байт", rs_bytes, block_len)));
+        }
+
+        let flags = u16::from_be_bytes([data[7], data[8]]);
+        if flags & !KNOWN_FLAGS != 0 {
+            return Err(Error::Protocol(format!("Неизвестные флаги заголовка: {:#06x}", flags)));
+        }
+
+        let file_size = u64::from_be_bytes(data[9..17].try_into().unwrap());
+        let interleave = data[17];
+        if interleave == 0 {
+            return Err(Error::Protocol("Некорректная глубина перемежения: 0".to_string()));
+        }
+        let fec = match data[18] {
+            FEC_REED_SOLOMON => Fec::ReedSolomon,
+            FEC_RAPTORQ => {
+                let symbol_size = u16::from_be_bytes([data[19], data[20]]);
+                let segment_symbols = u16::from_be_bytes([data[21], data[22]]);
+                if symbol_size == 0 || symbol_size as usize > u16::MAX as usize - 8 {
+                    return Err(Error::Protocol(format!("Некорректный размер символа RaptorQ: {}", symbol_size)));
+                }
+                if segment_symbols == 0 || segment_symbols > RAPTORQ_MAX_SYMBOLS {
+                    return Err(Error::Protocol(format!("Некорректное число символов в сегменте RaptorQ: {}", segment_symbols)));
+                }
+                // Приёмник держит сегмент в памяти целиком: больше, чем делает отправитель, не принимаем.
+                let segment_len = symbol_size as usize * segment_symbols as usize;
+                if segment_len > RAPTORQ_SEGMENT_LEN {
+                    return Err(Error::Protocol(format!(
+                        "Слишком большой сегмент RaptorQ: {} байт (не больше {})",
+                        segment_len, RAPTORQ_SEGMENT_LEN
+                    )));
+                }
+                Fec::RaptorQ { symbol_size, segment_symbols, repair_percent: data[23] }
+            }
+            mode => return Err(Error::Protocol(format!("Неизвестная схема коррекции ошибок: {}", mode))),
+        };
+        let transfer_id = u64::from_be_bytes(data[24..32].try_into().unwrap());
+        let copies = data[32];
+        if copies == 0 {
+            return Err(Error::Protocol("Некорректное число копий: 0".to_string()));
+        }
+        let fragment_len = u16::from_be_bytes([data[33], data[34]]);
+        if fragment_len != 0 && fec != Fec::ReedSolomon {
+            return Err(Error::Protocol("Фрагменты кадров поддерживаются только для RS".to_string()));
+        }
+        let name_len = data[HEADER_NAME_OFFSET - 1] as usize;
+        if HEADER_NAME_OFFSET + name_len > HEADER_CRC_OFFSET {
+            return Err(Error::Protocol(format!("Некорректная длина имени файла: {}", name_len)));
+        }
+        let filename = String::from_utf8_lossy(&data[HEADER_NAME_OFFSET..HEADER_NAME_OFFSET + name_len]).into_owned();
+
+        Ok(Header { version, rs_bytes, block_len, interleave, fec, flags, file_size, transfer_id, copies, fragment_len, filename })
+    }
+
+    pub fn block_count(&self) -> usize {
+        (self.file_size as usize).div_ceil(self.block_len as usize)
+    }
+
+    /// Размер сегмента RaptorQ в байтах, для RS — `None`.
+    pub fn segment_len(&self) -> Option<usize> {
+        match self.fec {
+            Fec::ReedSolomon => None,
+            Fec::RaptorQ { symbol_size, segment_symbols, .. } => Some(symbol_size as usize * segment_symbols as usize),
+        }
+    }
+
+    /// Сколько независимо восстанавливаемых частей в файле: RS-блоков или сегментов RaptorQ.
+    pub fn unit_count(&self) -> usize {
+        match self.segment_len() {
+            None => self.block_count(),
+            Some(segment_len) => (self.file_size as usize).div_ceil(segment_len),
+        }
+    }
+
+    pub fn group_count(&self) -> usize {
+        self.block_count().div_ceil(self.interleave as usize)
+    }
+
+    /// Номера RS-блоков, перемешанных в кадре `group`.
+    pub fn group_blocks(&self, group: usize) -> std::ops::Range<usize> {
+        let start = group * self.interleave as usize;
+        start..(start + self.interleave as usize).min(self.block_count())
+    }
+
+    /// Сколько кадров данных отводится на одну группу: номер кадра — `group * stride + fragment`.
+    pub fn group_stride(&self) -> usize {
+        match self.fragment_len {
+            0 => 1,
+            len => (self.interleave as usize * RS_CODEWORD_LEN).div_ceil(len as usize),
+        }
+    }
+
+    /// Длина перемешанной полезной нагрузки группы `group`.
+    pub fn group_payload_len(&self, group: usize) -> usize {
+        self.group_blocks(group).map(|i| self.codeword_len(i)).sum()
+    }
+
+    /// Байты файла, которые занимает часть `index`: RS-блок или сегмент RaptorQ.
+    pub fn unit_range(&self, index: usize) -> std::ops::Range<u64> {
+        let unit_len = self.segment_len().unwrap_or(self.block_len as usize) as u64;
+        let start = index as u64 * unit_len;
+        start..(start + unit_len).min(self.file_size)
+    }
+
+    /// Длина кодового слова блока `index`: все полные, кроме, возможно, последнего.
+    pub fn codeword_len(&self, index: usize) -> usize {
+        let block_len = self.block_len as usize;
+        let data_len = (self.file_size as usize - index * block_len).min(block_len);
+        data_len + self.rs_bytes as usize
+    }
+}
+
+/// Трейлер: SHA-256 исходного файла и CRC-32 от него, укороченное кодовое слово с `HEADER_ECC`.
+pub fn encode_trailer(digest: &[u8; 32]) -> Vec<u8> {
+    let mut data = [0u8; 36];
+    data[..32].copy_from_slice(digest);
+    data[32..].copy_from_slice(&crc32fast::hash(digest).to_be_bytes());
+    Encoder::new(HEADER_ECC).encode(&data).to_vec()
+}
+
+/// Извлекает SHA-256 файла из трейлера.
+pub fn decode_trailer(codeword: &[u8]) -> Result<[u8; 32]> {
+    if codeword.len() != 36 + HEADER_ECC {
+        return Err(Error::Decode(format!("Трейлер повреждён: длина {} байт", codeword.len())));
+    }
+    let corrected = Decoder::new(HEADER_ECC)
+        .correct(codeword, None)
+        .map_err(|e| Error::Decode(format!("Трейлер повреждён: {:?}", e)))?;
+    let data = corrected.data();
+    let digest: [u8; 32] = data[..32].try_into().unwrap();
+    if u32::from_be_bytes(data[32..].try_into().unwrap()) != crc32fast::hash(&digest) {
+        return Err(Error::Decode("Трейлер повреждён: неверная контрольная сумма".to_string()));
+    }
+    Ok(digest)
+}
+
+pub fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
@@ -0,0 +1,1181 @@
+//! Приём файлов: кадры разбираются непрерывно, части файла пишутся на диск по мере восстановления.
+
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::fmt;
+use std::ops::{Range, RangeInclusive};
+use std::fs::{File, OpenOptions, create_dir_all, remove_file, rename};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use chrono::Utc;
+use raptorq::{extended_source_block_symbols, ObjectTransmissionInformation, PayloadId, SourceBlockDecoder};
+use reed_solomon::Decoder;
+use sha2::{Digest, Sha256};
+
+use crate::error::{Error, Result};
+use crate::fec::deinterleave;
+use crate::gap::{unit_ranges, GapReport, GAP_DIGEST_LEN};
+use crate::journal::{journal_path, Journal, JOURNAL_INTERVAL};
+use crate::protocol::{
+    decode_trailer, hex, Fec, Frame, FrameReader, Header, FLAG_RESEND, FRAME_DATA, FRAME_HEADER, FRAME_TRAILER,
+};
+use crate::transport::{Endpoint, Rx};
+
+/// Битовая карта принятых частей файла: один бит на RS-блок или сегмент RaptorQ.
+#[derive(Clone)]
+pub struct BlockMap {
+    bits: Vec<u64>,
+    len: usize,
+}
+
+impl BlockMap {
+    pub fn new(len: usize) -> Self {
+        BlockMap { bits: vec![0; len.div_ceil(64)], len }
+    }
+
+    pub fn get(&self, index: usize) -> bool {
+        self.bits[index / 64] & (1 << (index % 64)) != 0
+    }
+
+    pub fn set(&mut self, index: usize) {
+        self.bits[index / 64] |= 1 << (index % 64);
+    }
+
+    pub fn clear(&mut self, index: usize) {
+        self.bits[index / 64] &= !(1 << (index % 64));
+    }
+
+    /// Карта байтами: часть `i` — бит `i % 8` байта `i / 8`.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.bits.iter().flat_map(|word| word.to_le_bytes()).take(self.len.div_ceil(8)).collect()
+    }
+
+    pub fn from_bytes(len: usize, bytes: &[u8]) -> Option<Self> {
+        if bytes.len() != len.div_ceil(8) {
+            return None;
+        }
+        let mut map = BlockMap::new(len);
+        for (i, &byte) in bytes.iter().enumerate() {
+            map.bits[i / 8] |= (byte as u64) << (8 * (i % 8));
+        }
+        Some(map)
+    }
+
+    /// Номера ещё не принятых частей.
+    pub fn missing(&self) -> impl Iterator<Item = usize> + '_ {
+        (0..self.len).filter(|&i| !self.get(i))
+    }
+}
+
+/// Сколько сегментов RaptorQ может декодироваться одновременно. Сегменты идут по порядку,
+/// и недособранный сегмент, вытесненный новыми, восстановится из следующей копии.
+const MAX_PARTIAL_SEGMENTS: usize = 8;
+
+/// Сколько групп RS, пришедших фрагментами, собирается одновременно. Группа, вытесненная
+/// новыми, декодируется с недостающими фрагментами в качестве стираний.
+const MAX_PARTIAL_GROUPS: usize = 8;
+
+/// Группа RS, собираемая из фрагментов: перемешанная нагрузка и отметки принятых фрагментов.
+struct PartialGroup {
+    payload: Vec<u8>,
+    fragments: Vec<bool>,
+}
+
+/// Работа кода коррекции за передачу. Рост исправлений при тех же параметрах — признак того,
+/// что линия деградирует, ещё до того, как начнут пропадать файлы.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DecodeStats {
+    /// Восстановлено RS-блоков или сегментов RaptorQ.
+    pub units: usize,
+    /// Исправлено ошибок в неизвестных местах. Из нескольких копий блока учитывается принятая.
+    pub corrected_symbols: u64,
+    /// Восстановлено стёртых байтов: отмеченных линией или из потерянных фрагментов.
+    /// Стирание стоит одного проверочного байта, а не двух, и в исправления не входит.
+    pub erased_symbols: u64,
+    /// Блоков, в которых было что исправлять или восстанавливать.
+    pub corrected_blocks: usize,
+    /// Сколько раз блок не удалось исправить в одной из копий.
+    pub uncorrectable: u64,
+    /// Блок с наибольшим числом исправленных ошибок и это число: оно не больше `correctable`.
+    pub worst_block: Option<(usize, usize)>,
+    /// Сколько ошибок без стираний исправляет RS-блок.
+    pub correctable: usize,
+    /// `histogram[k]` — сколько блоков принято с `k` исправленными ошибками.
+    pub histogram: Vec<usize>,
+    /// Пакетов RaptorQ, отброшенных по CRC.
+    pub rejected_packets: u64,
+    /// Байтов данных, принятых с ошибкой чётности или кадра и переданных декодеру как стирания.
+    pub line_marks: u64,
+}
+
+impl fmt::Display for DecodeStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.histogram.is_empty() {
+            return write!(f, "восстановлено сегментов {}, отброшено пакетов {}", self.units, self.rejected_packets);
+        }
+        write!(
+            f,
+            "исправлено ошибок {} в {} из {} блоков, не исправлено в копиях {}",
+            self.corrected_symbols, self.corrected_blocks, self.units, self.uncorrectable
+        )?;
+        if self.erased_symbols > 0 {
+            write!(f, ", восстановлено стираний {}", self.erased_symbols)?;
+        }
+        if self.line_marks > 0 {
+            write!(f, ", отмечено линией {}", self.line_marks)?;
+        }
+        match self.worst_block {
+            Some((index, errors)) => write!(f, ", худший блок {}: {} исправлений из {}", index, errors, self.correctable),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Собирает файл из кадров данных по схеме коррекции из заголовка, записывая
+/// восстановленные части сразу на свои места в файле `file`.
+struct Assembler {
+    header: Header,
+    file: File,
+    received: BlockMap,
+    /// Сколько ошибок и стираний исправлено в принятых RS-блоках, если хоть что-то. Из нескольких
+    /// копий оставляем блок с наименьшим числом ошибок, затем стираний: при ошибках сверх предела
+    /// декодер может «исправить» слово в неверное, и такое исправление почти всегда предельное.
+    corrected: HashMap<usize, (usize, usize)>,
+    rs: Decoder,
+    raptorq: BTreeMap<usize, SourceBlockDecoder>,
+    groups: BTreeMap<usize, PartialGroup>,
+    uncorrectable: u64,
+    rejected_packets: u64,
+    line_marks: u64,
+}
+
+impl Assembler {
+    fn new(header: Header, file: File) -> Self {
+        Assembler {
+            received: BlockMap::new(header.unit_count()),
+            corrected: HashMap::new(),
+            rs: Decoder::new(header.rs_bytes as usize),
+            raptorq: BTreeMap::new(),
+            groups: BTreeMap::new(),
+            uncorrectable: 0,
+            rejected_packets: 0,
+            line_marks: 0,
+            file,
+            header,
+        }
+    }
+
+    fn accept(&mut self, frame: &Frame) -> Result<()> {
+        match self.header.fec {
+            Fec::ReedSolomon if self.header.fragment_len > 0 => self.accept_fragment(frame.seq as usize, &frame.payload),
+            Fec::ReedSolomon if frame.erasures.is_empty() => self.accept_group(frame.seq as usize, &frame.payload, None),
+            Fec::ReedSolomon => {
+                self.line_marks += frame.erasures.len() as u64;
+                let mut erased = vec![false; frame.payload.len()];
+                frame.erasures.iter().for_each(|&pos| erased[pos] = true);
+                self.accept_group(frame.seq as usize, &frame.payload, Some(&erased))
+            }
+            Fec::RaptorQ { symbol_size, .. } => self.accept_packet(frame.seq as usize, symbol_size, &frame.payload),
+        }
+    }
+
+    /// Принят ли блок без единой исправленной ошибки.
+    fn is_clean(&self, index: usize) -> bool {
+        self.received.get(index) && !self.corrected.contains_key(&index)
+    }
+
+    fn write_unit(&mut self, index: usize, data: &[u8]) -> Result<()> {
+        self.file
+            .seek(SeekFrom::Start(self.header.unit_range(index).start))
+            .and_then(|_| self.file.write_all(data))
+            .map_err(Error::io("Ошибка записи файла"))?;
+        self.received.set(index);
+        Ok(())
+    }
+
+    fn accept_fragment(&mut self, seq: usize, payload: &[u8]) -> Result<()> {
+        let stride = self.header.group_stride();
+        let (group, fragment) = (seq / stride, seq % stride);
+        if group >= self.header.group_count() || self.header.group_blocks(group).all(|i| self.is_clean(i)) {
+            return Ok(());
+        }
+        let fragment_len = self.header.fragment_len as usize;
+        let total = self.header.group_payload_len(group);
+        let offset = fragment * fragment_len;
+        if offset >= total || payload.len() != fragment_len.min(total - offset) {
+            eprintln!("Фрагмент {} группы {} потерян: получено {} байт", fragment, group, payload.len());
+            return Ok(());
+        }
+
+        if !self.groups.contains_key(&group) && self.groups.len() >= MAX_PARTIAL_GROUPS {
+            let (oldest, partial) = self.groups.pop_first().unwrap();
+            self.decode_partial(oldest, partial)?;
+        }
+        let partial = self.groups.entry(group).or_insert_with(|| PartialGroup {
+            payload: vec![0; total],
+            fragments: vec![false; total.div_ceil(fragment_len)],
+        });
+        partial.payload[offset..offset + payload.len()].copy_from_slice(payload);
+        partial.fragments[fragment] = true;
+        if partial.fragments.iter().all(|&f| f) {
+            let partial = self.groups.remove(&group).unwrap();
+            self.accept_group(group, &partial.payload, None)?;
+        }
+        Ok(())
+    }
+
+    /// Декодирует группу, не все фрагменты которой пришли: байты потерянных фрагментов — стирания.
+    fn decode_partial(&mut self, group: usize, partial: PartialGroup) -> Result<()> {
+        let fragment_len = self.header.fragment_len as usize;
+        let erased: Vec<bool> = (0..partial.payload.len()).map(|pos| !partial.fragments[pos / fragment_len]).collect();
+        self.accept_group(group, &partial.payload, Some(&erased))
+    }
+
+    /// Декодирует все недособранные группы: больше их фрагментов в этой копии не будет.
+    fn flush(&mut self) -> Result<()> {
+        while let Some((group, partial)) = self.groups.pop_first() {
+            self.decode_partial(group, partial)?;
+        }
+        Ok(())
+    }
+
+    /// `erased` отмечает байты перемешанной нагрузки, которые не были приняты или приняты с ошибкой.
+    fn accept_group(&mut self, group: usize, payload: &[u8], erased: Option<&[bool]>) -> Result<()> {
+        if group >= self.header.group_count() {
+            return Ok(());
+        }
+        let indices = self.header.group_blocks(group);
+        if indices.clone().all(|i| self.is_clean(i)) {
+            return Ok(());
+        }
+        let lens: Vec<usize> = indices.clone().map(|i| self.header.codeword_len(i)).collect();
+        if payload.len() != lens.iter().sum::<usize>() {
+            eprintln!("Кадр {} потерян: получено {} байт", group, payload.len());
+            return Ok(());
+        }
+        let erasures: Vec<Vec<u8>> = match erased {
+            Some(erased) => deinterleave(erased, &lens)
+                .into_iter()
+                .map(|mask| mask.iter().enumerate().filter(|&(_, &e)| e).map(|(pos, _)| pos as u8).collect())
+                .collect(),
+            None => vec![Vec::new(); lens.len()],
+        };
+        for ((index, codeword), erasures) in indices.zip(deinterleave(payload, &lens)).zip(erasures) {
+            if self.is_clean(index) {
+                continue;
+            }
+            if erasures.len() > self.header.rs_bytes as usize {
+                eprintln!("Блок {} не удалось исправить: стёрто {} байт", index, erasures.len());
+                self.uncorrectable += 1;
+                continue;
+            }
+            let previous = match self.received.get(index) {
+                true => self.corrected[&index],
+                false => (usize::MAX, usize::MAX),
+            };
+            let erase_pos = (!erasures.is_empty()).then_some(erasures.as_slice());
+            match self.rs.correct(&codeword, erase_pos) {
+                Ok(corrected) => {
+                    let errors = corrected
+                        .iter()
+                        .zip(&codeword)
+                        .enumerate()
+                        .filter(|&(pos, (a, b))| a != b && erasures.binary_search(&(pos as u8)).is_err())
+                        .count();
+                    let correction = (errors, erasures.len());
+                    if correction < previous {
+                        self.write_unit(index, corrected.data())?;
+                        match correction {
+                            (0, 0) => self.corrected.remove(&index),
+                            _ => self.corrected.insert(index, correction),
+                        };
+                    }
+                }
+                Err(e) => {
+                    eprintln!("Блок {} не удалось исправить: {:?}", index, e);
+                    self.uncorrectable += 1;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn accept_packet(&mut self, segment: usize, symbol_size: u16, payload: &[u8]) -> Result<()> {
+        if segment >= self.header.unit_count() || self.received.get(segment) {
+            return Ok(());
+        }
+        let packet_len = 4 + symbol_size as usize;
+        if payload.len() != packet_len + 4
+            || u32::from_be_bytes(payload[packet_len..].try_into().unwrap()) != crc32fast::hash(&payload[..packet_len])
+        {
+            eprintln!("Пакет сегмента {} повреждён и отброшен", segment);
+            self.rejected_packets += 1;
+            return Ok(());
+        }
+
+        let segment_len = self.header.segment_len().unwrap();
+        let data_len = (self.header.file_size - segment as u64 * segment_len as u64).min(segment_len as u64) as usize;
+        let symbols = data_len.div_ceil(symbol_size as usize) as u32;
+        let id = PayloadId::deserialize(payload[..4].try_into().unwrap());
+        let esi = id.encoding_symbol_id();
+        if id.source_block_number() != 0 || (esi >= symbols && esi < extended_source_block_symbols(symbols)) {
+            return Ok(());
+        }
+
+        if !self.raptorq.contains_key(&segment) && self.raptorq.len() >= MAX_PARTIAL_SEGMENTS {
+            self.raptorq.pop_first();
+        }
+        let decoder = self.raptorq.entry(segment).or_insert_with(|| {
+            let config = ObjectTransmissionInformation::new(0, symbol_size, 0, 1, 1);
+            SourceBlockDecoder::new2(0, &config, symbols as u64 * symbol_size as u64)
+        });
+        let packet = raptorq::EncodingPacket::deserialize(&payload[..packet_len]);
+        if let Some(data) = decoder.decode([packet]) {
+            self.raptorq.remove(&segment);
+            self.write_unit(segment, &data[..data_len])?;
+        }
+        Ok(())
+    }
+
+    fn missing(&self) -> Vec<usize> {
+        self.received.missing().collect()
+    }
+
+    /// Считает принятыми все части, кроме перечисленных в отчёте о пропусках: они уже лежат в файле.
+    fn retain(&mut self, gap: &GapReport) {
+        (0..self.header.unit_count()).for_each(|index| self.received.set(index));
+        gap.units().for_each(|index| self.received.clear(index));
+    }
+
+    /// Заполняет нулями невосстановленные части, чтобы в частичном файле не осталось
+    /// ничего, кроме восстановленных данных.
+    fn zero_missing(&mut self, missing: &[usize]) -> Result<()> {
+        for &index in missing {
+            let range = self.header.unit_range(index);
+            self.file
+                .seek(SeekFrom::Start(range.start))
+                .and_then(|_| io::copy(&mut io::repeat(0).take(range.end - range.start), &mut self.file))
+                .map_err(Error::io("Ошибка записи файла"))?;
+        }
+        Ok(())
+    }
+
+    fn stats(&self) -> DecodeStats {
+        let units = self.header.unit_count() - self.received.missing().count();
+        let mut stats = DecodeStats {
+            units,
+            uncorrectable: self.uncorrectable,
+            rejected_packets: self.rejected_packets,
+            line_marks: self.line_marks,
+            ..DecodeStats::default()
+        };
+        if self.header.fec == Fec::ReedSolomon {
+            stats.correctable = self.header.rs_bytes as usize / 2;
+            stats.histogram = vec![0; stats.correctable + 1];
+            stats.histogram[0] = units - self.corrected.len();
+            for (&index, &(errors, erasures)) in &self.corrected {
+                stats.histogram[errors] += 1;
+                stats.corrected_symbols += errors as u64;
+                stats.erased_symbols += erasures as u64;
+                if errors == 0 {
+                    continue;
+                }
+                if stats.worst_block.is_none_or(|(worst, most)| errors > most || (errors == most && index < worst)) {
+                    stats.worst_block = Some((index, errors));
+                }
+            }
+            stats.corrected_blocks = self.corrected.len();
+        }
+        stats
+    }
+
+    fn is_complete(&self) -> bool {
+        self.received.missing().next().is_none()
+    }
+
+    fn digest(&mut self) -> Result<[u8; 32]> {
+        file_digest(&mut self.file)
+    }
+
+    /// Сбрасывает файл на диск.
+    fn sync(&self) -> Result<()> {
+        self.file.sync_all().map_err(Error::io("Ошибка записи файла"))
+    }
+}
+
+/// Временный файл приёма `*.part`: удаляется вместе с журналом, если передача не завершилась успешно.
+struct PartFile {
+    path: PathBuf,
+    keep: bool,
+}
+
+impl Drop for PartFile {
+    fn drop(&mut self) {
+        if !self.keep {
+            let _ = remove_file(&self.path);
+            let _ = remove_file(journal_path(&self.path));
+        }
+    }
+}
+
+/// Имя файла из заголовка пригодно только как последний компонент пути: разделители,
+/// управляющие символы и «..» заменяются, чтобы передача не могла писать вне выходной директории.
+pub fn sanitize_filename(name: &str) -> String {
+    let cleaned: String = name
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect();
+    let cleaned = cleaned.trim_matches(|c| c == '.' || c == ' ');
+    if cleaned.is_empty() {
+        "file".to_string()
+    } else {
+        cleaned.to_string()
+    }
+}
+
+/// Путь с добавленным расширением: к имеющемуся расширению добавляется `ext`.
+pub(crate) fn with_added_extension(path: &Path, ext: &str) -> PathBuf {
+    match path.extension() {
+        Some(old) => path.with_extension(format!("{}.{}", old.to_string_lossy(), ext)),
+        None => path.with_extension(ext),
+    }
+}
+
+/// Сбрасывает на диск запись директории, чтобы переименование пережило сбой питания.
+/// На Windows директорию так открыть нельзя, там это делает сама файловая система.
+fn sync_dir(dir: &Path) {
+    #[cfg(unix)]
+    if let Ok(dir) = File::open(dir) {
+        let _ = dir.sync_all();
+    }
+    #[cfg(not(unix))]
+    let _ = dir;
+}
+
+/// SHA-256 содержимого файла, прочитанного с начала.
+fn file_digest(file: &mut File) -> Result<[u8; 32]> {
+    file.seek(SeekFrom::Start(0)).map_err(Error::io("Ошибка чтения файла"))?;
+    let mut hasher = Sha256::new();
+    io::copy(file, &mut hasher).map_err(Error::io("Ошибка чтения файла"))?;
+    Ok(hasher.finalize().into())
+}
+
+/// Подряд идущие невосстановленные части файла и занимаемые ими байты `[начало, конец)`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DamagedRange {
+    pub units: RangeInclusive<usize>,
+    pub bytes: Range<u64>,
+}
+
+/// Карта повреждений частично сохранённого файла.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DamageMap {
+    /// Файл карты рядом с сохранённым файлом.
+    pub path: String,
+    /// Почему файл не прошёл проверку.
+    pub reason: String,
+    /// Заполненные нулями диапазоны. Пусто, если все части восстановлены, но SHA-256
+    /// не совпала или трейлер не получен: тогда места повреждений неизвестны.
+    pub ranges: Vec<DamagedRange>,
+    /// Что отправить заново, чтобы закончить файл (`sender resend --gap`).
+    pub gap: GapReport,
+}
+
+impl DamageMap {
+    pub fn damaged_bytes(&self) -> u64 {
+        self.ranges.iter().map(|range| range.bytes.end - range.bytes.start).sum()
+    }
+}
+
+impl fmt::Display for DamageMap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.ranges.len() {
+            0 => write!(f, "{}; места повреждений неизвестны", self.reason)?,
+            ranges => write!(f, "заполнено нулями {} байт в {} диапазонах", self.damaged_bytes(), ranges)?,
+        }
+        write!(f, ", карта: {}", self.path)
+    }
+}
+
+/// Результат приёма.
+#[derive(Debug, Clone)]
+pub struct ReceivedFile {
+    pub path: String,
+    /// SHA-256 файла в шестнадцатеричном виде, совпавший с присланным отправителем.
+    /// У частичного файла — SHA-256 сохранённых байтов.
+    pub sha256: String,
+    pub stats: DecodeStats,
+    /// Карта повреждений, если файл не восстановлен целиком и сохранён частично (`Receiver::set_salvage`).
+    pub damage: Option<Box<DamageMap>>,
+}
+
+/// Итог одной передачи, который выдаёт `Receiver::poll`.
+#[derive(Debug, Clone)]
+pub enum Event {
+    Received(ReceivedFile),
+    /// Передача закончилась, но файл не восстановлен или не прошёл проверку,
+    /// либо пришёл заголовок, который эта версия не принимает (`Error::Protocol`).
+    Failed(Arc<Error>),
+    /// Поток оборвался посреди передачи, и она ждёт продолжения: это ещё не итог. Позже
+    /// о той же передаче придёт `Received` или `Failed` — после продолжения, по концу
+    /// источника или через `Receiver::set_resume_timeout`.
+    Suspended(String),
+}
+
+/// Сколько последних завершённых передач помнит приёмник, чтобы не принимать их повторные копии заново.
+const COMPLETED_HISTORY: usize = 16;
+/// Сколько по умолчанию оборвавшаяся передача ждёт продолжения, прежде чем завершиться.
+pub const RESUME_TIMEOUT: Duration = Duration::from_secs(30 * 60);
+
+/// Принимаемая передача: заголовок, временный файл и собранные части.
+struct Session {
+    header: Header,
+    /// Номер копии, заголовок которой принят последним.
+    copy: u32,
+    assembler: Assembler,
+    trailer: Option<[u8; 32]>,
+    part: PartFile,
+    output_file: PathBuf,
+    /// Куда сохраняется файл, не прошедший проверку, в режиме спасения.
+    partial_file: PathBuf,
+    salvage: bool,
+    /// Частичный файл, который дополняет повторная отправка.
+    patching: Option<Partial>,
+    /// Когда журнал приёма обновлялся последний раз; `None` — передача без журнала.
+    journaled: Option<Instant>,
+}
+
+/// Частичный файл, сохранённый в режиме спасения, и отчёт о его пропусках из карты повреждений.
+struct Partial {
+    path: PathBuf,
+    map: PathBuf,
+    gap: GapReport,
+}
+
+/// Строка карты повреждений с кодом пропусков.
+const GAP_LINE: &str = "# Код пропусков: ";
+
+/// Ищет в `output_dir` частичный файл передачи `transfer_id` по картам повреждений.
+fn find_partial(output_dir: &str, transfer_id: u64) -> Result<Option<Partial>> {
+    let entries = match std::fs::read_dir(output_dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(Error::io("Ошибка чтения директории")(e)),
+    };
+    for entry in entries {
+        let map = entry.map_err(Error::io("Ошибка чтения директории"))?.path();
+        let Some(name) = map.file_name().and_then(|name| name.to_str()) else { continue };
+        let Some(partial_name) = name.strip_suffix(".damage") else { continue };
+        let Ok(text) = std::fs::read_to_string(&map) else { continue };
+        let gap = text.lines().find_map(|line| line.strip_prefix(GAP_LINE)).map(GapReport::decode);
+        if let Some(Ok(gap)) = gap
+            && gap.transfer_id == transfer_id
+        {
+            return Ok(Some(Partial { path: map.with_file_name(partial_name), map, gap }));
+        }
+    }
+    Ok(None)
+}
+
+impl Session {
+    fn start(header: Header, copy: u32, output_dir: &str, salvage: bool, journal: bool, patching: Option<Partial>) -> Result<Self> {
+        log_header(&header);
+        if header.copies > 1 {
+            eprintln!("Передача {:016x}: {} копий, принимаем с копии {}", header.transfer_id, header.copies, copy + 1);
+        }
+
+        // Части файла пишутся сразу на диск во временный файл; под окончательным именем
+        // он появится только после проверки контрольной суммы.
+        let timestamp = Utc::now().format("%Y%m%d_%H%M%S").to_string();
+        let output_file = Path::new(output_dir).join(format!("decoded_{}_{}", timestamp, sanitize_filename(&header.filename)));
+        let (part, partial_file, file) = match &patching {
+            // Повторная отправка дописывает недостающие части прямо в частичный файл:
+            // восстановленное раньше должно пережить и неудачу повтора.
+            Some(partial) => {
+                let rs_bytes = (header.fec == Fec::ReedSolomon).then_some(header.rs_bytes);
+                if partial.gap.file_size != header.file_size || partial.gap.rs_bytes != rs_bytes {
+                    return Err(Error::Protocol(format!(
+                        "Повторная отправка не совпадает с частичным файлом {}",
+                        partial.path.display()
+                    )));
+                }
+                let file = OpenOptions::new()
+                    .read(true)
+                    .write(true)
+                    .open(&partial.path)
+                    .map_err(Error::io("Ошибка открытия частичного файла"))?;
+                eprintln!("Дополняем частичный файл: {}, недостаёт частей: {}", partial.path.display(), partial.gap.units().count());
+                (PartFile { path: partial.path.clone(), keep: true }, partial.path.clone(), file)
+            }
+            None => {
+                let part = PartFile { path: with_added_extension(&output_file, "part"), keep: false };
+                create_dir_all(output_dir).map_err(Error::io("Ошибка создания директории"))?;
+                let file = OpenOptions::new()
+                    .read(true)
+                    .write(true)
+                    .create_new(true)
+                    .open(&part.path)
+                    .map_err(Error::io("Ошибка создания файла"))?;
+                file.set_len(header.file_size).map_err(Error::io("Ошибка создания файла"))?;
+                eprintln!("Принимаем во временный файл: {}", part.path.display());
+                let partial_file =
+                    Path::new(output_dir).join(format!("partial_{}_{}", timestamp, sanitize_filename(&header.filename)));
+                (part, partial_file, file)
+            }
+        };
+
+        let mut assembler = Assembler::new(header.clone(), file);
+        if let Some(partial) = &patching {
+            assembler.retain(&partial.gap);
+        }
+        // Повторная отправка пишет в частичный файл, который и так переживёт перезапуск.
+        let journaled = (journal && patching.is_none()).then(Instant::now);
+        let mut session = Session {
+            assembler,
+            header,
+            copy,
+            trailer: None,
+            part,
+            output_file,
+            partial_file,
+            salvage: salvage || patching.is_some(),
+            patching,
+            journaled,
+        };
+        session.save_journal()?;
+        Ok(session)
+    }
+
+    /// Продолжает передачу, принятую до перезапуска, по её журналу и временному файлу `part`.
+    fn restore(journal: Journal, part: PathBuf, copy: u32, output_dir: &str, salvage: bool) -> Result<Self> {
+        let header = journal.header;
+        log_header(&header);
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(&part)
+            .map_err(Error::io("Ошибка открытия временного файла"))?;
+        let part = PartFile { path: part, keep: false };
+        if file.metadata().map_err(Error::io("Ошибка открытия временного файла"))?.len() != header.file_size {
+            return Err(Error::Integrity(format!("Временный файл {} не совпадает с журналом", part.path.display())));
+        }
+        let mut assembler = Assembler::new(header.clone(), file);
+        assembler.received = journal.received;
+        assembler.corrected = journal.corrected.into_iter().map(|(index, errors, erasures)| (index, (errors, erasures))).collect();
+        eprintln!(
+            "Продолжаем приём по журналу: {}, не восстановлено: {} из {}",
+            part.path.display(),
+            assembler.missing().len(),
+            header.unit_count()
+        );
+        Ok(Session {
+            assembler,
+            header,
+            copy,
+            trailer: journal.trailer,
+            part,
+            output_file: Path::new(output_dir).join(journal.output_name),
+            partial_file: Path::new(output_dir).join(journal.partial_name),
+            salvage,
+            patching: None,
+            journaled: Some(Instant::now()),
+        })
+    }
+
+    /// Сбрасывает принятое на диск и записывает журнал, если передача его ведёт.
+    fn save_journal(&mut self) -> Result<()> {
+        if self.journaled.is_none() {
+            return Ok(());
+        }
+        self.assembler.file.sync_data().map_err(Error::io("Ошибка записи файла"))?;
+        let name = |path: &Path| path.file_name().unwrap_or_default().to_string_lossy().into_owned();
+        Journal {
+            header: self.header.clone(),
+            trailer: self.trailer,
+            output_name: name(&self.output_file),
+            partial_name: name(&self.partial_file),
+            received: self.assembler.received.clone(),
+            corrected: self.assembler.corrected.iter().map(|(&index, &(errors, erasures))| (index, errors, erasures)).collect(),
+        }
+        .save(&journal_path(&self.part.path))?;
+        self.journaled = Some(Instant::now());
+        Ok(())
+    }
+
+    /// Обновляет журнал не чаще раза в `JOURNAL_INTERVAL`.
+    fn keep_journal(&mut self) -> Result<()> {
+        match self.journaled {
+            Some(saved) if saved.elapsed() >= JOURNAL_INTERVAL => self.save_journal(),
+            _ => Ok(()),
+        }
+    }
+
+    /// Поток оборвался посреди передачи: части ещё не собраны, и это не повторная отправка пропусков.
+    fn interrupted(&self) -> bool {
+        self.patching.is_none() && !self.assembler.is_complete()
+    }
+
+    fn unit_name(&self) -> &'static str {
+        match self.header.fec {
+            Fec::ReedSolomon => "блоков",
+            Fec::RaptorQ { .. } => "сегментов",
+        }
+    }
+
+    /// Все части восстановлены и их SHA-256 совпадает с трейлером.
+    fn verified(&mut self) -> Result<bool> {
+        self.assembler.flush()?;
+        match self.trailer {
+            Some(expected) if self.assembler.is_complete() => Ok(self.assembler.digest()? == expected),
+            _ => Ok(false),
+        }
+    }
+
+    /// Проверяет файл и переименовывает его в окончательное имя. В режиме спасения
+    /// непроверенный файл сохраняется частично.
+    fn finish(mut self) -> Result<ReceivedFile> {
+        self.assembler.flush()?;
+        let stats = self.assembler.stats();
+        eprintln!("Коррекция: {}", stats);
+        if stats.corrected_blocks > 0 {
+            let counts: Vec<String> = stats
+                .histogram
+                .iter()
+                .enumerate()
+                .filter(|&(_, &blocks)| blocks > 0)
+                .map(|(errors, blocks)| format!("{}: {}", errors, blocks))
+                .collect();
+            eprintln!("Блоков по числу исправлений: {}", counts.join(", "));
+        }
+        let missing = self.assembler.missing();
+        let (sha256, damage) = match self.verify(&missing) {
+            Ok(digest) => (hex(&digest), None),
+            Err(e) if self.salvage => {
+                eprintln!("{}", e);
+                self.assembler.zero_missing(&missing)?;
+                self.output_file = std::mem::take(&mut self.partial_file);
+                let damage = self.write_damage_map(&missing, e.to_string())?;
+                (hex(&self.assembler.digest()?), Some(Box::new(damage)))
+            }
+            Err(e) => return Err(e),
+        };
+
+        self.assembler.sync()?;
+        eprintln!("Сохраняем файл: {}", self.output_file.display());
+        if self.part.path != self.output_file {
+            rename(&self.part.path, &self.output_file).map_err(Error::io("Ошибка переименования файла"))?;
+        }
+        self.part.keep = true;
+        let _ = remove_file(journal_path(&self.part.path));
+        if let Some(dir) = self.output_file.parent() {
+            sync_dir(dir);
+        }
+        if let (Some(partial), None) = (&self.patching, &damage) {
+            let _ = remove_file(&partial.map);
+            let _ = remove_file(qr_path(&partial.path));
+            eprintln!("Частичный файл дополнен до целого");
+        }
+
+        match &damage {
+            Some(damage) => eprintln!("Файл сохранён частично: {}", damage),
+            None => eprintln!("Файл успешно сохранён"),
+        }
+        Ok(ReceivedFile { path: self.output_file.to_string_lossy().into_owned(), sha256, stats, damage })
+    }
+
+    /// Проверяет, что все части восстановлены и SHA-256 файла совпадает с трейлером.
+    fn verify(&mut self, missing: &[usize]) -> Result<[u8; 32]> {
+        if !missing.is_empty() {
+            return Err(Error::Decode(format!(
+                "Не восстановлено {}: {} из {} (первый: {})",
+                self.unit_name(),
+                missing.len(),
+                self.header.unit_count(),
+                missing[0]
+            )));
+        }
+        eprintln!(
+            "Данные декодированы: {} {}, размер: {} байт",
+            self.header.unit_count(),
+            self.unit_name(),
+            self.header.file_size
+        );
+
+        let expected = self
+            .trailer
+            .ok_or_else(|| Error::Integrity("Не получен трейлер с контрольной суммой файла".to_string()))?;
+        let actual = self.assembler.digest()?;
+        if actual != expected {
+            return Err(Error::Integrity(format!(
+                "Контрольная сумма не совпадает: ожидалась SHA-256 {}, получена {}",
+                hex(&expected),
+                hex(&actual)
+            )));
+        }
+        eprintln!("SHA-256 совпадает: {}", hex(&actual));
+        Ok(actual)
+    }
+
+    /// Пишет карту повреждений рядом с частичным файлом: по строке на диапазон
+    /// подряд идущих невосстановленных частей — начало и конец в байтах и их номера,
+    /// а также код пропусков для повторной отправки и его QR-код.
+    fn write_damage_map(&self, missing: &[usize], reason: String) -> Result<DamageMap> {
+        let ranges: Vec<DamagedRange> = unit_ranges(missing.iter().copied())
+            .into_iter()
+            .map(|units| {
+                let bytes = self.header.unit_range(*units.start()).start..self.header.unit_range(*units.end()).end;
+                DamagedRange { units, bytes }
+            })
+            .collect();
+        // Все части восстановлены, но SHA-256 не сошлась: неизвестно, какая испорчена, нужны все.
+        let gap_units = match (missing.is_empty(), self.trailer) {
+            (true, Some(_)) => unit_ranges(0..self.header.unit_count()),
+            _ => ranges.iter().map(|range| range.units.clone()).collect(),
+        };
+        let gap = GapReport {
+            transfer_id: self.header.transfer_id,
+            file_size: self.header.file_size,
+            rs_bytes: (self.header.fec == Fec::ReedSolomon).then_some(self.header.rs_bytes),
+            digest: self.trailer.map(|digest| digest[..GAP_DIGEST_LEN].try_into().unwrap()),
+            missing: gap_units,
+        };
+
+        let unit_len = self.header.segment_len().unwrap_or(self.header.block_len as usize);
+        let mut text = format!(
+            "# Частично принятый файл: {}\n# Причина: {}\n# Передача: {:016x}\n# SHA-256 из трейлера: {}\n{}{}\n# {} по {} байт; байты [начало, конец) заполнены нулями\n# начало конец части\n",
+            self.output_file.file_name().unwrap_or_default().to_string_lossy(),
+            reason,
+            self.header.transfer_id,
+            self.trailer.map_or_else(|| "не получена".to_string(), |digest| hex(&digest)),
+            GAP_LINE,
+            gap.encode(),
+            match self.header.fec {
+                Fec::ReedSolomon => "Блоки RS",
+                Fec::RaptorQ { .. } => "Сегменты RaptorQ",
+            },
+            unit_len
+        );
+        for range in &ranges {
+            text += &match range.units.start() == range.units.end() {
+                true => format!("{} {} {}\n", range.bytes.start, range.bytes.end, range.units.start()),
+                false => format!("{} {} {}-{}\n", range.bytes.start, range.bytes.end, range.units.start(), range.units.end()),
+            };
+        }
+        let path = with_added_extension(&self.output_file, "damage");
+        std::fs::write(&path, text).map_err(Error::io("Ошибка записи карты повреждений"))?;
+        match gap.qr_svg() {
+            Ok(svg) => std::fs::write(qr_path(&self.output_file), svg).map_err(Error::io("Ошибка записи QR-кода"))?,
+            Err(e) => eprintln!("{}", e),
+        }
+        Ok(DamageMap { path: path.to_string_lossy().into_owned(), reason, ranges, gap })
+    }
+}
+
+/// QR-код с кодом пропусков рядом с частичным файлом.
+fn qr_path(file: &Path) -> PathBuf {
+    with_added_extension(file, "gap.svg")
+}
+
+fn log_header(header: &Header) {
+    match header.fec {
+        Fec::ReedSolomon => eprintln!(
+            "Заголовок: версия {}, RS {} байт, блок {} байт, перемежение {}, флаги {:#06x}, имя файла: {}",
+            header.version, header.rs_bytes, header.block_len, header.interleave, header.flags, header.filename
+        ),
+        Fec::RaptorQ { symbol_size, segment_symbols, repair_percent } => eprintln!(
+            "Заголовок: версия {}, RaptorQ: символ {} байт, {} символов в сегменте, избыточность {}%, флаги {:#06x}, имя файла: {}",
+            header.version, symbol_size, segment_symbols, repair_percent, header.flags, header.filename
+        ),
+    }
+    if header.fragment_len > 0 {
+        eprintln!("Группы RS приходят фрагментами по {} байт", header.fragment_len);
+    }
+    eprintln!("Размер файла: {} байт", header.file_size);
+}
+
+/// Состояние приёмника между кадрами.
+enum State {
+    /// Ждём заголовок новой передачи.
+    Idle,
+    /// Принят заголовок копии, кадров данных после него ещё не было.
+    Header(Session),
+    Data(Session),
+    /// Принят трейлер копии; ждём заголовок следующей копии или паузу в потоке.
+    Trailer(Session),
+}
+
+/// Приёмник, который держит порт открытым между передачами: байты, пришедшие между
+/// двумя файлами, не теряются. `poll` читает по кадру и сообщает о каждой завершённой передаче.
+pub struct Receiver {
+    reader: FrameReader<Box<dyn Rx>>,
+    output_dir: String,
+    state: State,
+    completed: VecDeque<u64>,
+    salvage: bool,
+    /// Вести журнал приёма, чтобы после перезапуска продолжить передачу.
+    journal: bool,
+    /// Оборвавшаяся передача, которая ждёт продолжения от `sender resume`, до `suspended_until`.
+    suspended: Option<Session>,
+    suspended_until: Instant,
+    resume_timeout: Duration,
+    /// Событие, которое вернёт следующий `poll`.
+    pending: Option<Event>,
+}
+
+impl Receiver {
+    /// Открывает транспорт по адресу `port` (см. `Endpoint::parse`).
+    pub fn open(port: &str, baud: u32, output_dir: &str) -> Result<Self> {
+        Ok(Receiver::new(Endpoint::parse(port)?.open_rx(baud)?, output_dir))
+    }
+
+    pub fn new(rx: Box<dyn Rx>, output_dir: &str) -> Self {
+        Receiver {
+            reader: FrameReader::new(rx),
+            output_dir: output_dir.to_string(),
+            state: State::Idle,
+            completed: VecDeque::new(),
+            salvage: false,
+            journal: true,
+            suspended: None,
+            suspended_until: Instant::now(),
+            resume_timeout: RESUME_TIMEOUT,
+            pending: None,
+        }
+    }
+
+    /// Режим спасения: файл, который не удалось восстановить целиком, сохраняется с нулями
+    /// на месте невосстановленных частей под именем `partial_*` и с картой повреждений `*.damage`.
+    pub fn set_salvage(&mut self, salvage: bool) {
+        self.salvage = salvage;
+    }
+
+    /// Вести ли журнал приёма; по умолчанию ведётся. Без журнала приёмник ничего не оставляет
+    /// в выходной директории: закрытый посреди передачи, он удаляет её временный файл,
+    /// а журналы прошлых запусков не ищет.
+    pub fn set_journal(&mut self, journal: bool) {
+        self.journal = journal;
+    }
+
+    /// Сколько оборвавшаяся передача ждёт продолжения; по умолчанию `RESUME_TIMEOUT`.
+    pub fn set_resume_timeout(&mut self, timeout: Duration) {
+        self.resume_timeout = timeout;
+    }
+
+    /// Обрабатывает один кадр или паузу в потоке. Ошибки передачи приходят событием
+    /// `Event::Failed`, а `Err` означает, что перестал работать сам транспорт
+    /// (`Error::Closed` — источник закончился).
+    pub fn poll(&mut self) -> Result<Option<Event>> {
+        if let Some(event) = self.pending.take() {
+            return Ok(Some(event));
+        }
+        // Продолжения не дождались: передача завершается с тем, что принято.
+        if let Some(session) = self.suspended.take_if(|_| Instant::now() >= self.suspended_until) {
+            eprintln!("Передача {:016x} не продолжена за {:?}, завершается", session.header.transfer_id, self.resume_timeout);
+            return Ok(Some(self.finish(session)));
+        }
+        let frame = match self.reader.next_frame() {
+            Ok(frame) => frame,
+            // Пауза в потоке: отправитель закончил, ждать остальных копий бессмысленно. Если же
+            // поток оборвался посреди передачи, она откладывается до продолжения.
+            // Конец потока завершает текущую и отложенную передачи, а затем сообщает о закрытии.
+            Err(e) if matches!(e.kind(), io::ErrorKind::TimedOut | io::ErrorKind::UnexpectedEof) => {
+                let eof = e.kind() == io::ErrorKind::UnexpectedEof;
+                return match std::mem::replace(&mut self.state, State::Idle) {
+                    State::Idle if eof => match self.suspended.take() {
+                        Some(session) => Ok(Some(self.finish(session))),
+                        None => Err(Error::Closed),
+                    },
+                    State::Idle => Ok(None),
+                    State::Header(session) | State::Data(session) | State::Trailer(session)
+                        if !eof && session.interrupted() =>
+                    {
+                        Ok(Some(self.suspend(session)))
+                    }
+                    State::Header(session) | State::Data(session) | State::Trailer(session) => {
+                        Ok(Some(self.finish(session)))
+                    }
+                };
+            }
+            Err(e) => return Err(Error::io("Ошибка чтения кадра")(e)),
+        };
+
+        let (state, event) = match (std::mem::replace(&mut self.state, State::Idle), frame.kind) {
+            (state, FRAME_HEADER) => self.on_header(state, &frame),
+            (State::Idle, _) => (State::Idle, None),
+            (State::Header(mut session) | State::Data(mut session) | State::Trailer(mut session), FRAME_DATA) => {
+                match session.assembler.accept(&frame).and_then(|()| session.keep_journal()) {
+                    Ok(()) => (State::Data(session), None),
+                    Err(e) => (State::Idle, Some(self.fail(session, e))),
+                }
+            }
+            (State::Header(session) | State::Data(session) | State::Trailer(session), FRAME_TRAILER) => {
+                self.on_trailer(session, &frame)
+            }
+            (state, _) => (state, None),
+        };
+        self.state = state;
+        Ok(event)
+    }
+
+    fn on_header(&mut self, state: State, frame: &Frame) -> (State, Option<Event>) {
+        let header = match Header::decode(&frame.payload) {
+            Ok(header) => header,
+            // Заголовок прочитан верно, но принять такую передачу нельзя: об этом надо знать
+            // оператору. Текущую передачу чужой заголовок не прерывает.
+            Err(e @ Error::Protocol(_)) => {
+                eprintln!("{}", e);
+                return (state, Some(Event::Failed(Arc::new(e))));
+            }
+            Err(e) => {
+                eprintln!("{}", e);
+                return (state, None);
+            }
+        };
+        let previous = match state {
+            State::Header(mut session) | State::Data(mut session) | State::Trailer(mut session)
+                if session.header.transfer_id == header.transfer_id =>
+            {
+                if frame.seq != session.copy {
+                    session.copy = frame.seq;
+                    eprintln!(
+                        "Копия {} из {}, не восстановлено: {}",
+                        session.copy + 1,
+                        session.header.copies,
+                        session.assembler.missing().len()
+                    );
+                }
+                return (State::Header(session), None);
+            }
+            State::Idle => None,
+            State::Header(session) | State::Data(session) | State::Trailer(session) => {
+                eprintln!("Начата новая передача, текущая завершается");
+                Some(session)
+            }
+        };
+        let event = previous.map(|session| self.finish(session));
+        if let Some(mut session) = self.suspended.take_if(|session| session.header == header) {
+            session.copy = frame.seq;
+            eprintln!(
+                "Продолжение передачи {:016x} с копии {}, не восстановлено: {}",
+                header.transfer_id,
+                session.copy + 1,
+                session.assembler.missing().len()
+            );
+            return (State::Header(session), event);
+        }
+        // Повторная отправка идёт под идентификатором уже завершённой передачи и нужна,
+        // только пока её частичный файл не дополнен.
+        let patching = if header.flags & FLAG_RESEND != 0 {
+            match find_partial(&self.output_dir, header.transfer_id) {
+                Ok(Some(partial)) => Some(partial),
+                Ok(None) => {
+                    eprintln!("Повторная отправка передачи {:016x}: частичного файла нет, пропускаем", header.transfer_id);
+                    return (State::Idle, event);
+                }
+                Err(e) => return (State::Idle, event.or(Some(Event::Failed(Arc::new(e))))),
+            }
+        } else if self.completed.contains(&header.transfer_id) {
+            return (State::Idle, event);
+        } else if let Some((part, journal)) = self.journal.then(|| Journal::find(&self.output_dir, &header)).flatten() {
+            return match Session::restore(journal, part, frame.seq, &self.output_dir, self.salvage) {
+                Ok(session) => (State::Header(session), event),
+                Err(e) => (State::Idle, event.or(Some(Event::Failed(Arc::new(e))))),
+            };
+        } else {
+            None
+        };
+        match Session::start(header, frame.seq, &self.output_dir, self.salvage, self.journal, patching) {
+            Ok(session) => (State::Header(session), event),
+            Err(e) => (State::Idle, event.or(Some(Event::Failed(Arc::new(e))))),
+        }
+    }
+
+    fn on_trailer(&mut self, mut session: Session, frame: &Frame) -> (State, Option<Event>) {
+        match decode_trailer(&frame.payload) {
+            Ok(digest) => session.trailer = Some(digest),
+            Err(e) => eprintln!("{}", e),
+        }
+        if session.copy + 1 >= session.header.copies as u32 {
+            return (State::Idle, Some(self.finish(session)));
+        }
+        // Файл уже собран и сошёлся с трейлером — остальные копии не нужны.
+        match session.verified().and_then(|verified| session.save_journal().map(|()| verified)) {
+            Ok(true) => (State::Idle, Some(self.finish(session))),
+            Ok(false) => (State::Trailer(session), None),
+            Err(e) => (State::Idle, Some(self.fail(session, e))),
+        }
+    }
+
+    /// Откладывает оборвавшуюся передачу: временный файл и собранные части хранятся, пока
+    /// не придёт заголовок той же передачи от `sender resume` или не истечёт `resume_timeout`.
+    /// Отложенная раньше передача завершается.
+    fn suspend(&mut self, mut session: Session) -> Event {
+        let message = format!(
+            "Передача {:016x} прервана, не восстановлено {}: {} из {}; принятое хранится до продолжения",
+            session.header.transfer_id,
+            session.unit_name(),
+            session.assembler.missing().len(),
+            session.header.unit_count()
+        );
+        eprintln!("{}", message);
+        if let Err(e) = session.save_journal() {
+            eprintln!("{}", e);
+        }
+        self.suspended_until = Instant::now() + self.resume_timeout;
+        if let Some(previous) = self.suspended.replace(session) {
+            self.pending = Some(self.finish(previous));
+        }
+        Event::Suspended(message)
+    }
+
+    fn remember(&mut self, transfer_id: u64) {
+        if self.completed.len() == COMPLETED_HISTORY {
+            self.completed.pop_front();
+        }
+        self.completed.push_back(transfer_id);
+    }
+
+    fn finish(&mut self, session: Session) -> Event {
+        self.remember(session.header.transfer_id);
+        let skipped = self.reader.take_skipped();
+        if skipped > 0 {
+            eprintln!("Пропущено байтов при поиске синхронизации: {}", skipped);
+        }
+        match session.finish() {
+            Ok(file) => Event::Received(file),
+            Err(e) => Event::Failed(Arc::new(e)),
+        }
+    }
+
+    fn fail(&mut self, session: Session, error: Error) -> Event {
+        self.remember(session.header.transfer_id);
+        Event::Failed(Arc::new(error))
+    }
+}
+
+/// Приёмник, закрытый посреди передачи, оставляет её временный файл и журнал:
+/// следующий запуск продолжит её, когда придёт её заголовок. Без журнала
+/// (`Receiver::set_journal`) временный файл удаляется.
+impl Drop for Receiver {
+    fn drop(&mut self) {
+        let current = match std::mem::replace(&mut self.state, State::Idle) {
+            State::Idle => None,
+            State::Header(session) | State::Data(session) | State::Trailer(session) => Some(session),
+        };
+        for mut session in current.into_iter().chain(self.suspended.take()) {
+            if session.journaled.is_none() {
+                continue;
+            }
+            match session.save_journal() {
+                Ok(()) => session.part.keep = true,
+                Err(e) => eprintln!("{}", e),
+            }
+        }
+    }
+}
@@ -0,0 +1,314 @@
+//! Последовательный порт на удалённом сервере портов по RFC 2217 (Telnet COM-PORT-OPTION).
+//!
+//! Параметры линии согласуются по Telnet, дальше по соединению идёт обычный поток кадров:
+//! байт 0xFF в данных удваивается, команды Telnet из потока сервера вырезаются.
+//! Чётность линии согласуется, но отметок о байтах с ошибкой чётности RFC 2217 не передаёт.
+
+use std::io::{self, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::Instant;
+
+use serialport::Parity;
+
+use crate::error::{Error, Result};
+use crate::port::{SerialConfig, PORT_TIMEOUT};
+use crate::transport::{Rx, Tx};
+
+const IAC: u8 = 255;
+const DONT: u8 = 254;
+const DO: u8 = 253;
+const WONT: u8 = 252;
+const WILL: u8 = 251;
+const SB: u8 = 250;
+const SE: u8 = 240;
+
+const OPT_BINARY: u8 = 0;
+const OPT_SGA: u8 = 3;
+const OPT_COM_PORT: u8 = 44;
+
+/// Команды COM-PORT-OPTION; ответ сервера на команду приходит с кодом больше на 100.
+const SET_BAUDRATE: u8 = 1;
+const SET_DATASIZE: u8 = 2;
+const SET_PARITY: u8 = 3;
+const SET_STOPSIZE: u8 = 4;
+const SET_CONTROL: u8 = 5;
+const SERVER_OFFSET: u8 = 100;
+
+/// Параметры линии диода: 8 бит данных, 1 стоповый бит, без управления потоком; чётность — из адреса.
+const DATASIZE: u8 = 8;
+const PARITY_NONE: u8 = 1;
+const PARITY_ODD: u8 = 2;
+const PARITY_EVEN: u8 = 3;
+const STOPSIZE_ONE: u8 = 1;
+const CONTROL_NONE: u8 = 1;
+
+/// Разбор входящего потока Telnet.
+#[derive(Clone, Copy)]
+enum Telnet {
+    Data,
+    Iac,
+    /// Принята команда согласования (`WILL`/`WONT`/`DO`/`DONT`), ждём номер опции.
+    Negotiate(u8),
+    Sub,
+    SubIac,
+}
+
+/// Соединение с сервером портов. Пишет и читает, как локальный последовательный порт.
+pub struct Rfc2217Port {
+    stream: TcpStream,
+    state: Telnet,
+    /// Тело текущей подопции (`IAC SB ... IAC SE`) без `IAC SB`.
+    sub: Vec<u8>,
+    /// Данные, вырезанные из потока сервера и ещё не прочитанные.
+    data: Vec<u8>,
+    data_pos: usize,
+    /// Подтверждённая сервером скорость.
+    confirmed_baud: Option<u32>,
+    /// Подтверждённые сервером параметры линии: команда и значение.
+    confirmed: Vec<(u8, u8)>,
+    refused: bool,
+}
+
+impl Rfc2217Port {
+    /// Подключается к `config.name` (`ХОСТ:ПОРТ`) и настраивает порт на `baud`, 8 бит данных,
+    /// чётность из `config` и 1 стоповый бит.
+    pub fn open(config: &SerialConfig, baud: u32) -> Result<Self> {
+        let addr = config.name.as_str();
+        eprintln!("Подключаемся к серверу портов: {} с baud {}", config, baud);
+        let socket_addr = addr
+            .to_socket_addrs()
+            .map_err(|e| Error::Policy(format!("Некорректный адрес сервера портов {}: {}", addr, e)))?
+            .next()
+            .ok_or_else(|| Error::Policy(format!("Адрес сервера портов не найден: {}", addr)))?;
+        let stream = TcpStream::connect_timeout(&socket_addr, PORT_TIMEOUT)
+            .map_err(Error::io("Не удалось подключиться к серверу портов"))?;
+        let setup = || -> io::Result<()> {
+            stream.set_read_timeout(Some(PORT_TIMEOUT))?;
+            stream.set_write_timeout(Some(PORT_TIMEOUT))?;
+            stream.set_nodelay(true)
+        };
+        setup().map_err(Error::io("Не удалось подключиться к серверу портов"))?;
+        let mut port = Rfc2217Port {
+            stream,
+            state: Telnet::Data,
+            sub: Vec::new(),
+            data: Vec::new(),
+            data_pos: 0,
+            confirmed_baud: None,
+            confirmed: Vec::new(),
+            refused: false,
+        };
+        port.negotiate(baud, config.parity)?;
+        Ok(port)
+    }
+
+    /// Объявляет опции Telnet, задаёт параметры линии и ждёт, пока сервер их подтвердит.
+    /// Данные, пришедшие до подтверждения, не теряются: они останутся в буфере для `read`.
+    fn negotiate(&mut self, baud: u32, parity: Parity) -> Result<()> {
+        let mut out = vec![
+            IAC, WILL, OPT_COM_PORT, IAC, WILL, OPT_BINARY, IAC, DO, OPT_BINARY, IAC, WILL, OPT_SGA, IAC, DO, OPT_SGA,
+        ];
+        let parity = match parity {
+            Parity::None => PARITY_NONE,
+            Parity::Odd => PARITY_ODD,
+            Parity::Even => PARITY_EVEN,
+        };
+        let line = [(SET_DATASIZE, DATASIZE), (SET_PARITY, parity), (SET_STOPSIZE, STOPSIZE_ONE), (SET_CONTROL, CONTROL_NONE)];
+        line_setting(SET_BAUDRATE, &baud.to_be_bytes(), &mut out);
+        for (command, value) in line {
+            line_setting(command, &[value], &mut out);
+        }
+        self.stream.write_all(&out).map_err(Error::io("Ошибка согласования с сервером портов"))?;
+
+        let deadline = Instant::now() + PORT_TIMEOUT;
+        let settled = |port: &Self| {
+            port.refused
+                || port.confirmed_baud.is_some() && line.iter().all(|&(command, _)| port.confirmed.iter().any(|&(c, _)| c == command))
+        };
+        while !settled(self) && Instant::now() < deadline {
+            match self.receive() {
+                Ok(0) => return Err(Error::Closed),
+                Ok(_) => {}
+                Err(e) if e.kind() == io::ErrorKind::TimedOut => break,
+                Err(e) => return Err(Error::io("Ошибка согласования с сервером портов")(e)),
+            }
+        }
+        if self.refused {
+            return Err(Error::Protocol("Сервер портов не поддерживает RFC 2217".to_string()));
+        }
+        // Данные, чётность и стоповые биты должны совпасть с линией отправителя, иначе каждый байт
+        // придёт с ошибкой. Сервер отвечает значением, которое установил; без ответа верим запросу,
+        // кроме чётности: по умолчанию серверы работают без неё.
+        for (command, value) in line {
+            match self.confirmed.iter().rev().find(|&&(c, _)| c == command) {
+                Some(&(_, confirmed)) if confirmed != value => {
+                    return Err(Error::Policy(format!(
+                        "Сервер портов не установил {}: {} вместо {}",
+                        setting_name(command),
+                        confirmed,
+                        value
+                    )));
+                }
+                None if command == SET_PARITY && value != PARITY_NONE => {
+                    return Err(Error::Policy("Сервер портов не подтвердил чётность линии".to_string()));
+                }
+                _ => {}
+            }
+        }
+        match self.confirmed_baud {
+            Some(confirmed) if confirmed != baud => eprintln!("Сервер портов установил скорость {} вместо {}", confirmed, baud),
+            Some(_) => eprintln!("Сервер портов подтвердил скорость {}", baud),
+            None => eprintln!("Сервер портов не подтвердил скорость, продолжаем с запрошенной"),
+        }
+        Ok(())
+    }
+
+    /// Просит сервер сменить скорость. Подтверждения не ждём: в потоке могут идти данные,
+    /// а ответ сервера разберёт `receive`.
+    fn request_baud(&mut self, baud: u32) -> io::Result<()> {
+        let mut out = Vec::new();
+        line_setting(SET_BAUDRATE, &baud.to_be_bytes(), &mut out);
+        self.stream.write_all(&out)
+    }
+
+    /// Читает порцию от сервера: данные добавляет в буфер, команды Telnet обрабатывает сам.
+    /// Возвращает число прочитанных сырых байтов, 0 — соединение закрыто.
+    fn receive(&mut self) -> io::Result<usize> {
+        let mut chunk = [0u8; 4096];
+        let n = self.stream.read(&mut chunk).map_err(|e| match e.kind() {
+            // Таймаут чтения сокета на Unix приходит как WouldBlock.
+            io::ErrorKind::WouldBlock => io::ErrorKind::TimedOut.into(),
+            _ => e,
+        })?;
+        self.data.drain(..self.data_pos);
+        self.data_pos = 0;
+        let mut replies = Vec::new();
+        for &byte in &chunk[..n] {
+            self.state = match (self.state, byte) {
+                (Telnet::Data, IAC) => Telnet::Iac,
+                (Telnet::Data, _) => {
+                    self.data.push(byte);
+                    Telnet::Data
+                }
+                (Telnet::Iac, IAC) => {
+                    self.data.push(IAC);
+                    Telnet::Data
+                }
+                (Telnet::Iac, WILL | WONT | DO | DONT) => Telnet::Negotiate(byte),
+                (Telnet::Iac, SB) => {
+                    self.sub.clear();
+                    Telnet::Sub
+                }
+                (Telnet::Iac, _) => Telnet::Data,
+                (Telnet::Negotiate(command), option) => {
+                    self.on_negotiate(command, option, &mut replies);
+                    Telnet::Data
+                }
+                (Telnet::Sub, IAC) => Telnet::SubIac,
+                (Telnet::Sub, _) => {
+                    self.sub.push(byte);
+                    Telnet::Sub
+                }
+                (Telnet::SubIac, SE) => {
+                    self.on_subnegotiation();
+                    Telnet::Data
+                }
+                (Telnet::SubIac, _) => {
+                    self.sub.push(byte);
+                    Telnet::Sub
+                }
+            };
+        }
+        if !replies.is_empty() {
+            self.stream.write_all(&replies)?;
+        }
+        Ok(n)
+    }
+
+    /// Свои опции мы объявили сами и на их подтверждение не отвечаем; остальные отклоняем.
+    fn on_negotiate(&mut self, command: u8, option: u8, replies: &mut Vec<u8>) {
+        let ours = matches!(option, OPT_BINARY | OPT_SGA | OPT_COM_PORT);
+        match command {
+            DONT if option == OPT_COM_PORT => self.refused = true,
+            DO | WILL if !ours => replies.extend_from_slice(&[IAC, if command == DO { WONT } else { DONT }, option]),
+            _ => {}
+        }
+    }
+
+    fn on_subnegotiation(&mut self) {
+        match self.sub[..] {
+            [OPT_COM_PORT, command, a, b, c, d] if command == SET_BAUDRATE + SERVER_OFFSET => {
+                self.confirmed_baud = Some(u32::from_be_bytes([a, b, c, d]));
+            }
+            [OPT_COM_PORT, command, value] if command > SERVER_OFFSET => self.confirmed.push((command - SERVER_OFFSET, value)),
+            _ => {}
+        }
+    }
+}
+
+fn setting_name(command: u8) -> &'static str {
+    match command {
+        SET_DATASIZE => "число бит данных",
+        SET_PARITY => "чётность",
+        SET_STOPSIZE => "число стоповых бит",
+        _ => "управление потоком",
+    }
+}
+
+/// Подопция COM-PORT-OPTION с параметром линии.
+fn line_setting(command: u8, value: &[u8], out: &mut Vec<u8>) {
+    out.extend_from_slice(&[IAC, SB, OPT_COM_PORT, command]);
+    escape_into(value, out);
+    out.extend_from_slice(&[IAC, SE]);
+}
+
+/// Удваивает байты 0xFF, чтобы сервер не принял данные за команду Telnet.
+fn escape_into(data: &[u8], out: &mut Vec<u8>) {
+    for &byte in data {
+        out.push(byte);
+        if byte == IAC {
+            out.push(IAC);
+        }
+    }
+}
+
+impl Write for Rfc2217Port {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut out = Vec::with_capacity(buf.len() + buf.len() / 64);
+        escape_into(buf, &mut out);
+        self.stream.write_all(&out)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.stream.flush()
+    }
+}
+
+impl Read for Rfc2217Port {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        // Порция от сервера может состоять из одних команд Telnet: читаем, пока не придут данные.
+        // Разрыв соединения — сбой транспорта, а не конец потока: приёмник переподключится.
+        while self.data_pos == self.data.len() {
+            if self.receive()? == 0 {
+                return Err(io::Error::new(io::ErrorKind::ConnectionAborted, "Сервер портов закрыл соединение"));
+            }
+        }
+        let n = buf.len().min(self.data.len() - self.data_pos);
+        buf[..n].copy_from_slice(&self.data[self.data_pos..self.data_pos + n]);
+        self.data_pos += n;
+        Ok(n)
+    }
+}
+
+impl Tx for Rfc2217Port {
+    fn set_baud_rate(&mut self, baud: u32) -> io::Result<()> {
+        self.request_baud(baud)
+    }
+}
+
+impl Rx for Rfc2217Port {
+    fn set_baud_rate(&mut self, baud: u32) -> io::Result<()> {
+        self.request_baud(baud)
+    }
+}
@@ -0,0 +1,360 @@
+//! Проверка линии: отправитель шлёт известную псевдослучайную последовательность (PRBS-31),
+//! приёмник сравнивает принятое с ожидаемым и считает ошибки бит, потери байтов и BER.
+//! В режиме перебора скоростей расписание идёт в каждом кадре, и приёмник переключается
+//! вслед за отправителем без обратного канала.
+
+use std::fmt;
+use std::io;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use reed_solomon::{Decoder, Encoder};
+
+use crate::error::{Error, Result};
+use crate::port::PORT_TIMEOUT;
+use crate::protocol::{encode_frame, FrameReader, FRAME_HEADER_LEN, FRAME_SELFTEST};
+use crate::transport::{Rx, Tx};
+
+/// Сколько скоростей можно перебрать за одну проверку.
+pub const MAX_SWEEP_STEPS: usize = 16;
+/// Номер шага, число шагов, длительность шага и остаток шага в мс, скорости шагов.
+const SCHEDULE_DATA_LEN: usize = 2 + 4 + 4 + 4 * MAX_SWEEP_STEPS;
+/// Расписание защищено своим RS-кодом: по нему приёмник переключает скорость.
+const SCHEDULE_ECC: usize = 16;
+const SCHEDULE_LEN: usize = SCHEDULE_DATA_LEN + SCHEDULE_ECC;
+/// Байтов PRBS в кадре. Кадр целиком помещается в самую короткую датаграмму UDP.
+pub const PRBS_LEN: usize = 160;
+const SELFTEST_FRAME_LEN: usize = FRAME_HEADER_LEN + SCHEDULE_LEN + PRBS_LEN;
+/// Пауза между шагами: приёмник успевает сменить скорость, даже если ждал данных весь таймаут порта.
+pub const SWEEP_GUARD: Duration = Duration::from_secs(PORT_TIMEOUT.as_secs() + 1);
+/// Приёмник меняет скорость чуть позже конца шага: хвост шага ещё может быть в пути.
+const SWITCH_DELAY: Duration = Duration::from_millis(500);
+/// Бит на байт в линии 8N1.
+const LINE_BITS_PER_BYTE: u64 = 10;
+
+/// Генератор PRBS-31 (x^31 + x^28 + 1), биты выдаются старшим вперёд.
+#[derive(Debug, Clone)]
+pub struct Prbs31(u32);
+
+impl Prbs31 {
+    /// Нулевое состояние регистр не покидает, поэтому заменяется единицей.
+    pub fn new(seed: u32) -> Self {
+        match seed & 0x7FFF_FFFF {
+            0 => Prbs31(1),
+            state => Prbs31(state),
+        }
+    }
+
+    pub fn next_byte(&mut self) -> u8 {
+        let mut byte = 0;
+        for _ in 0..8 {
+            let bit = ((self.0 >> 30) ^ (self.0 >> 27)) & 1;
+            self.0 = ((self.0 << 1) | bit) & 0x7FFF_FFFF;
+            byte = (byte << 1) | bit as u8;
+        }
+        byte
+    }
+}
+
+/// Кусок PRBS для кадра `seq`: у каждого кадра своё начальное состояние, поэтому потеря
+/// кадра не сбивает сравнение следующих.
+pub fn prbs_block(seq: u32) -> [u8; PRBS_LEN] {
+    let mut prbs = Prbs31::new(seq.wrapping_mul(0x9E37_79B1) ^ 0x2545_F491);
+    let mut block = [0u8; PRBS_LEN];
+    block.iter_mut().for_each(|b| *b = prbs.next_byte());
+    block
+}
+
+/// Что и сколько проверять: одна скорость или перебор нескольких, по `step` на каждую.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SelfTestPlan {
+    pub bauds: Vec<u32>,
+    pub step: Duration,
+}
+
+impl SelfTestPlan {
+    fn check(&self) -> Result<()> {
+        if self.bauds.is_empty() || self.bauds.len() > MAX_SWEEP_STEPS {
+            return Err(Error::Policy(format!("Число скоростей должно быть от 1 до {}: {}", MAX_SWEEP_STEPS, self.bauds.len())));
+        }
+        if self.bauds.contains(&0) {
+            return Err(Error::Policy("Скорость не может быть нулевой".to_string()));
+        }
+        if self.step.is_zero() || self.step.as_millis() > u32::MAX as u128 {
+            return Err(Error::Policy(format!("Некорректная длительность шага: {:?}", self.step)));
+        }
+        Ok(())
+    }
+}
+
+/// Расписание в кадре: какой сейчас шаг и сколько ему осталось.
+struct Schedule {
+    step: usize,
+    step_len: Duration,
+    remaining: Duration,
+    bauds: Vec<u32>,
+}
+
+impl Schedule {
+    fn encode(&self) -> Vec<u8> {
+        let mut data = [0u8; SCHEDULE_DATA_LEN];
+        data[0] = self.step as u8;
+        data[1] = self.bauds.len() as u8;
+        data[2..6].copy_from_slice(&(self.step_len.as_millis() as u32).to_be_bytes());
+        data[6..10].copy_from_slice(&(self.remaining.as_millis() as u32).to_be_bytes());
+        for (i, baud) in self.bauds.iter().enumerate() {
+            data[10 + 4 * i..14 + 4 * i].copy_from_slice(&baud.to_be_bytes());
+        }
+        Encoder::new(SCHEDULE_ECC).encode(&data).to_vec()
+    }
+
+    fn decode(codeword: &[u8]) -> Option<Self> {
+        let corrected = Decoder::new(SCHEDULE_ECC).correct(codeword, None).ok()?;
+        let data = corrected.data();
+        let (step, count) = (data[0] as usize, data[1] as usize);
+        if count == 0 || count > MAX_SWEEP_STEPS || step >= count {
+            return None;
+        }
+        let millis = |range: std::ops::Range<usize>| Duration::from_millis(u32::from_be_bytes(data[range].try_into().unwrap()) as u64);
+        let bauds: Vec<u32> = (0..count).map(|i| u32::from_be_bytes(data[10 + 4 * i..14 + 4 * i].try_into().unwrap())).collect();
+        if bauds.contains(&0) {
+            return None;
+        }
+        Some(Schedule { step, step_len: millis(2..6), remaining: millis(6..10), bauds })
+    }
+}
+
+/// Шлёт PRBS по плану: на каждой скорости `plan.step`, между скоростями пауза `SWEEP_GUARD`.
+/// Транспорт должен быть открыт на первой скорости плана. Поток не быстрее линии 8N1,
+/// чтобы расписание в кадрах не расходилось с тем, что реально ушло в линию.
+pub fn send_selftest(tx: &mut dyn Tx, plan: &SelfTestPlan) -> Result<()> {
+    plan.check()?;
+    for (step, &baud) in plan.bauds.iter().enumerate() {
+        if step > 0 {
+            tx.set_baud_rate(baud).map_err(Error::io("Не удалось сменить скорость"))?;
+            thread::sleep(SWEEP_GUARD);
+        }
+        eprintln!("Проверка линии: скорость {}, {} с", baud, plan.step.as_secs_f64());
+        let started = Instant::now();
+        let mut seq: u32 = 0;
+        loop {
+            let elapsed = started.elapsed();
+            if elapsed >= plan.step {
+                break;
+            }
+            let schedule = Schedule { step, step_len: plan.step, remaining: plan.step - elapsed, bauds: plan.bauds.clone() };
+            let mut payload = schedule.encode();
+            payload.extend_from_slice(&prbs_block(seq));
+            tx.write_all(&encode_frame(FRAME_SELFTEST, seq, &payload)).map_err(Error::io("Ошибка записи"))?;
+            seq += 1;
+
+            let due = Duration::from_secs_f64((seq as u64 * SELFTEST_FRAME_LEN as u64 * LINE_BITS_PER_BYTE) as f64 / baud as f64);
+            if let Some(wait) = due.checked_sub(started.elapsed()) {
+                thread::sleep(wait);
+            }
+        }
+        tx.flush().map_err(Error::io("Ошибка записи"))?;
+        eprintln!("Скорость {}: отправлено кадров {}", baud, seq);
+    }
+    Ok(())
+}
+
+/// Итог проверки на одной скорости. Считается с первого принятого кадра шага.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct StepReport {
+    pub baud: u32,
+    pub frames: u64,
+    /// Кадры, пропавшие целиком или со сбитыми служебными полями.
+    pub lost_frames: u64,
+    /// Сравнено бит PRBS в принятых кадрах.
+    pub bits: u64,
+    pub bit_errors: u64,
+    /// Сколько байтов не дошло, включая пропавшие кадры, и сколько лишних нашлось внутри кадров.
+    pub lost_bytes: u64,
+    pub extra_bytes: u64,
+}
+
+impl StepReport {
+    /// Доля ошибочных бит среди сравненных.
+    pub fn ber(&self) -> Option<f64> {
+        (self.bits > 0).then(|| self.bit_errors as f64 / self.bits as f64)
+    }
+
+    /// Кадры приняты без единой ошибки, потери или лишнего байта.
+    pub fn is_clean(&self) -> bool {
+        self.frames > 0 && self.lost_frames == 0 && self.bit_errors == 0 && self.lost_bytes == 0 && self.extra_bytes == 0
+    }
+}
+
+impl fmt::Display for StepReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Some(ber) = self.ber() else {
+            return write!(f, "Скорость {}: приёма нет", self.baud);
+        };
+        write!(
+            f,
+            "Скорость {}: кадров {}, потеряно кадров {}, проверено бит {}, ошибок бит {}, BER {:.2e}, потеряно байт {}, лишних байт {}",
+            self.baud, self.frames, self.lost_frames, self.bits, self.bit_errors, ber, self.lost_bytes, self.extra_bytes
+        )
+    }
+}
+
+/// Сколько байтов может потеряться или добавиться в одном месте кадра, чтобы сравнение их нашло.
+const MAX_SLIP: usize = 4;
+/// Сдвиг признаётся, только если убирает заметно больше ошибок, чем случайный шум. Если кадр
+/// короче ожидаемого, потерю уже показала длина, и сдвиг на столько же байтов порога не требует.
+/// Лишний байт у самого конца кадра от шума не отличить: он считается ошибками бит.
+const SLIP_MIN_GAIN: u64 = 16;
+
+/// Сравнивает принятый кусок PRBS с ожидаемым. Потерянный или лишний байт внутри кадра
+/// сдвигает остаток, и прямое сравнение насчитало бы половину бит ошибочными, поэтому ищем
+/// одно место сдвига с наименьшим числом ошибок. Возвращает сравненные биты, ошибки и сдвиг:
+/// больше нуля — лишние байты, меньше нуля — потерянные.
+fn compare(received: &[u8], expected: &[u8]) -> (u64, u64, isize) {
+    let diff = |i: usize, j: usize| (received[i] ^ expected[j]).count_ones() as u64;
+    let aligned = received.len().min(expected.len());
+    let mut prefix = vec![0u64; aligned + 1];
+    for i in 0..aligned {
+        prefix[i + 1] = prefix[i] + diff(i, i);
+    }
+    let mut best = (aligned as u64 * 8, prefix[aligned], 0isize);
+    let shortfall = expected.len().saturating_sub(received.len()) as isize;
+    for slip in (1..=MAX_SLIP as isize).flat_map(|d| [d, -d]) {
+        let (skip_received, skip_expected) = if slip > 0 { (slip as usize, 0) } else { (0, slip.unsigned_abs()) };
+        let len = received.len().saturating_sub(skip_received).min(expected.len().saturating_sub(skip_expected));
+        // tail[p] — ошибки от места сдвига p до конца.
+        let mut tail = vec![0u64; len + 1];
+        for p in (0..len).rev() {
+            tail[p] = tail[p + 1] + diff(p + skip_received, p + skip_expected);
+        }
+        let gain = if slip == -shortfall { 0 } else { SLIP_MIN_GAIN };
+        for p in 0..=len.min(aligned) {
+            let errors = prefix[p] + tail[p];
+            if errors + gain < best.1 {
+                best = (len as u64 * 8, errors, slip);
+            }
+        }
+    }
+    best
+}
+
+/// Принимает PRBS и считает ошибки, следуя расписанию из кадров.
+struct SelfTestReceiver {
+    reader: FrameReader<Box<dyn Rx>>,
+    schedule: Option<(Vec<u32>, Duration)>,
+    step: usize,
+    step_end: Option<Instant>,
+    report: StepReport,
+    /// Первый и последний номер кадра, принятые на этом шаге.
+    seqs: Option<(u32, u32)>,
+    reports: Vec<StepReport>,
+}
+
+impl SelfTestReceiver {
+    fn on_frame(&mut self, seq: u32, payload: &[u8]) {
+        if let Some(schedule) = payload.get(..SCHEDULE_LEN).and_then(Schedule::decode)
+            && schedule.step >= self.step
+        {
+            if schedule.step > self.step {
+                // Переход на этот шаг прошёл мимо нас, но кадры его читаются: начинаем его здесь.
+                self.finish_step();
+                self.step = schedule.step;
+            }
+            self.report.baud = schedule.bauds[schedule.step];
+            self.step_end = Some(Instant::now() + schedule.remaining);
+            self.schedule = Some((schedule.bauds, schedule.step_len));
+        }
+
+        self.seqs = match self.seqs {
+            None => {
+                eprintln!("Скорость {}: поток захвачен", self.report.baud);
+                Some((seq, seq))
+            }
+            Some((first, last)) if seq > last => Some((first, seq)),
+            // Повтор или кадр из прошлого шага не считаем.
+            Some(_) => return,
+        };
+        self.report.frames += 1;
+
+        let expected = prbs_block(seq);
+        let received = payload.get(SCHEDULE_LEN..).unwrap_or(&[]);
+        let (bits, errors, slip) = compare(received, &expected);
+        self.report.bits += bits;
+        self.report.bit_errors += errors;
+        self.report.lost_bytes += PRBS_LEN.saturating_sub(received.len()) as u64;
+        self.report.extra_bytes += slip.max(0) as u64;
+    }
+
+    /// Подводит итог шага и начинает следующий на той же скорости.
+    fn finish_step(&mut self) {
+        if let Some((first, last)) = self.seqs {
+            self.report.lost_frames = ((last - first) as u64 + 1).saturating_sub(self.report.frames);
+            self.report.lost_bytes += self.report.lost_frames * SELFTEST_FRAME_LEN as u64;
+        }
+        eprintln!("{}", self.report);
+        let baud = self.report.baud;
+        self.reports.push(std::mem::replace(&mut self.report, StepReport { baud, ..StepReport::default() }));
+        self.seqs = None;
+    }
+
+    /// Переходит на следующий шаг расписания; `false`, если шагов больше нет.
+    fn next_step(&mut self, end: Instant) -> Result<bool> {
+        self.finish_step();
+        let Some((bauds, step_len)) = &self.schedule else {
+            return Ok(false);
+        };
+        if self.step + 1 >= bauds.len() {
+            return Ok(false);
+        }
+        self.step += 1;
+        let baud = bauds[self.step];
+        self.step_end = Some(end + SWEEP_GUARD + *step_len);
+        self.report.baud = baud;
+        eprintln!("Переходим на скорость {}", baud);
+        self.reader.get_mut().set_baud_rate(baud).map_err(Error::io("Не удалось сменить скорость"))?;
+        Ok(true)
+    }
+
+    fn run(mut self) -> Result<Vec<StepReport>> {
+        loop {
+            if let Some(end) = self.step_end
+                && Instant::now() >= end + SWITCH_DELAY
+                && !self.next_step(end)?
+            {
+                return Ok(self.reports);
+            }
+            match self.reader.next_frame() {
+                Ok(frame) if frame.kind == FRAME_SELFTEST => self.on_frame(frame.seq, &frame.payload),
+                Ok(_) => {}
+                // Без расписания конец проверки узнаём по тишине после принятых кадров.
+                Err(e) if e.kind() == io::ErrorKind::TimedOut => {
+                    if self.step_end.is_none() && self.seqs.is_some() {
+                        self.finish_step();
+                        return Ok(self.reports);
+                    }
+                }
+                Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => {
+                    self.finish_step();
+                    return Ok(self.reports);
+                }
+                Err(e) => return Err(Error::io("Ошибка чтения")(e)),
+            }
+        }
+    }
+}
+
+/// Принимает проверку линии, начиная со скорости `baud`, на которой открыт транспорт.
+/// Возвращает итог каждого шага; ждёт первого кадра сколько угодно.
+pub fn receive_selftest(rx: Box<dyn Rx>, baud: u32) -> Result<Vec<StepReport>> {
+    eprintln!("Ждём проверочный поток на скорости {}", baud);
+    SelfTestReceiver {
+        reader: FrameReader::new(rx),
+        schedule: None,
+        step: 0,
+        step_end: None,
+        report: StepReport { baud, ..StepReport::default() },
+        seqs: None,
+        reports: Vec::new(),
+    }
+    .run()
+}
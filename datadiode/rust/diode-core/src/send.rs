@@ -0,0 +1,515 @@
+//! Отправка файла: кадры заголовка, данных и трейлера пишутся в порт по мере чтения файла.
+
+use std::collections::BTreeSet;
+use std::fs::File;
+use std::io::{self, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use sha2::{Digest, Sha256};
+
+use crate::checkpoint::{Checkpoint, Tracker, RESUME_OVERLAP};
+use crate::error::{Error, Result};
+use crate::fec::{
+    encode_blocks, encode_segment, interleave, rs_block_len, RAPTORQ_SEGMENT_LEN, RS_CODEWORD_LEN, RAPTORQ_SEGMENT_SYMBOLS,
+    RAPTORQ_SYMBOL_SIZE,
+};
+use crate::gap::{GapReport, GAP_DIGEST_LEN};
+use crate::protocol::{
+    encode_frame, encode_trailer, hex, Fec, Header, FLAG_RESEND, FRAME_DATA, FRAME_HEADER, FRAME_HEADER_LEN,
+    FRAME_TRAILER, MAX_NAME_LEN, PROTOCOL_VERSION,
+};
+use crate::transport::{Endpoint, Tx};
+
+/// Схема коррекции ошибок, которую выбирает отправитель.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FecMode {
+    /// Блоки RS с `rs_bytes` проверочными байтами, перемешанные по `interleave` в кадре.
+    ReedSolomon,
+    /// Фонтанный код: файл режется на сегменты, к пакетам каждого сегмента добавляется
+    /// `repair_percent` процентов ремонтных пакетов. Приёмнику хватает любых пакетов в количестве
+    /// чуть больше исходного, повреждённые пакеты отбрасываются по CRC.
+    RaptorQ { repair_percent: u8 },
+}
+
+pub const DEFAULT_REPAIR_PERCENT: u8 = 20;
+
+/// Параметры кодирования, выбираемые отправителем на каждую передачу.
+#[derive(Debug, Clone)]
+pub struct SendOptions {
+    pub rs_bytes: u8,
+    pub interleave: u8,
+    pub fec: FecMode,
+    /// Сколько раз отправить файл целиком: на односторонней линии это единственный способ
+    /// перекрыть потери, которые не исправил код.
+    pub repeat: u8,
+    /// Файл контрольной точки: отправка записывает в него, докуда дошла, чтобы прерванную
+    /// передачу можно было продолжить `resume`.
+    pub checkpoint: Option<PathBuf>,
+}
+
+impl Default for SendOptions {
+    fn default() -> Self {
+        SendOptions { rs_bytes: 10, interleave: 1, fec: FecMode::ReedSolomon, repeat: 1, checkpoint: None }
+    }
+}
+
+impl Header {
+    /// `fragment_len` — длина фрагмента группы RS в кадре, 0 — группа целиком в одном кадре.
+    pub fn new(options: &SendOptions, file_size: u64, filename: &str, fragment_len: u16) -> Result<Self> {
+        let block_len = rs_block_len(options.rs_bytes)? as u8;
+        if options.interleave == 0 {
+            return Err(Error::Policy("Глубина перемежения должна быть не меньше 1".to_string()));
+        }
+        if options.repeat == 0 {
+            return Err(Error::Policy("Число повторов должно быть не меньше 1".to_string()));
+        }
+        // Номер кадра данных — номер фрагмента группы блоков или сегмента, он должен поместиться в u32.
+        let units = match options.fec {
+            FecMode::ReedSolomon => {
+                let groups = file_size.div_ceil(block_len as u64).div_ceil(options.interleave as u64);
+                let stride = match fragment_len {
+                    0 => 1,
+                    len => (options.interleave as u64 * RS_CODEWORD_LEN as u64).div_ceil(len as u64),
+                };
+                groups * stride
+            }
+            FecMode::RaptorQ { .. } => file_size.div_ceil(RAPTORQ_SEGMENT_LEN as u64),
+        };
+        if units > u32::MAX as u64 {
+            return Err(Error::Policy("Файл слишком большой для выбранных параметров кодирования".to_string()));
+        }
+        Ok(Header {
+            version: PROTOCOL_VERSION,
+            rs_bytes: options.rs_bytes,
+            block_len,
+            interleave: options.interleave,
+            fec: match options.fec {
+                FecMode::ReedSolomon => Fec::ReedSolomon,
+                FecMode::RaptorQ { repair_percent } => Fec::RaptorQ {
+                    symbol_size: RAPTORQ_SYMBOL_SIZE,
+                    segment_symbols: RAPTORQ_SEGMENT_SYMBOLS,
+                    repair_percent,
+                },
+            },
+            flags: 0,
+            file_size,
+            transfer_id: new_transfer_id(),
+            copies: options.repeat,
+            fragment_len: match options.fec {
+                FecMode::ReedSolomon => fragment_len,
+                FecMode::RaptorQ { .. } => 0,
+            },
+            filename: truncate_name(filename, MAX_NAME_LEN).to_string(),
+        })
+    }
+}
+
+/// Случайный идентификатор передачи: `RandomState` засевается случайными ключами процесса.
+fn new_transfer_id() -> u64 {
+    use std::hash::{BuildHasher, Hasher};
+    let mut hasher = std::collections::hash_map::RandomState::new().build_hasher();
+    hasher.write_u128(
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos(),
+    );
+    hasher.finish()
+}
+
+/// Обрезает имя до `max` байт, не разрывая символ UTF-8.
+fn truncate_name(name: &str, max: usize) -> &str {
+    if name.len() <= max {
+        return name;
+    }
+    let mut end = max;
+    while !name.is_char_boundary(end) {
+        end -= 1;
+    }
+    &name[..end]
+}
+
+/// Размер буфера записи в порт: кадры уходят пачками, а не отдельными вызовами.
+const WRITE_BUFFER_LEN: usize = 64 * 1024;
+
+/// Через сколько кадров данных повторяется заголовок копии: приёмник, перезапущенный
+/// посреди передачи, продолжает её по журналу, не дожидаясь следующей копии.
+pub const HEADER_REPEAT_FRAMES: u32 = 256;
+
+/// Читает до `len` байт; меньше — только в конце файла.
+fn read_chunk(file: &mut File, len: usize, buf: &mut Vec<u8>) -> Result<()> {
+    buf.clear();
+    (&mut *file)
+        .take(len as u64)
+        .read_to_end(buf)
+        .map_err(Error::io("Ошибка чтения файла"))?;
+    Ok(())
+}
+
+/// Кадры данных одной группы RS-блоков или одного сегмента RaptorQ с номером `seq`.
+fn encode_chunk(header: &Header, seq: u32, chunk: &[u8]) -> Result<Vec<(u32, Vec<u8>)>> {
+    let stride = header.group_stride() as u32;
+    Ok(match header.fec {
+        Fec::ReedSolomon if header.fragment_len > 0 => {
+            let payload = interleave(&encode_blocks(chunk, header.rs_bytes)?);
+            payload
+                .chunks(header.fragment_len as usize)
+                .enumerate()
+                .map(|(fragment, part)| (seq * stride + fragment as u32, part.to_vec()))
+                .collect()
+        }
+        Fec::ReedSolomon => vec![(seq, interleave(&encode_blocks(chunk, header.rs_bytes)?))],
+        Fec::RaptorQ { symbol_size, repair_percent, .. } => {
+            encode_segment(chunk, symbol_size, repair_percent).into_iter().map(|packet| (seq, packet)).collect()
+        }
+    })
+}
+
+/// Пишет кадр данных, а после каждых `HEADER_REPEAT_FRAMES` кадров — кадр заголовка копии `copy_header`.
+fn write_data(out: &mut impl Write, copy_header: &[u8], frames: &mut u32, seq: u32, payload: &[u8]) -> Result<()> {
+    out.write_all(&encode_frame(FRAME_DATA, seq, payload))
+        .map_err(Error::io("Ошибка отправки данных"))?;
+    *frames = frames.wrapping_add(1);
+    if frames.is_multiple_of(HEADER_REPEAT_FRAMES) {
+        out.write_all(copy_header).map_err(Error::io("Ошибка отправки заголовка"))?;
+    }
+    Ok(())
+}
+
+/// Длина куска файла, из которого собирается один номер кадров: группа RS-блоков или сегмент.
+fn chunk_len(header: &Header) -> usize {
+    header.segment_len().unwrap_or(header.block_len as usize * header.interleave as usize)
+}
+
+/// Отправляет одну копию тела передачи: кадры данных и трейлер. Файл читается кусками
+/// по одной группе RS-блоков или одному сегменту RaptorQ, так что память не зависит от размера файла.
+fn send_body(out: &mut impl Write, file: &mut File, header: &Header, copy_header: &[u8]) -> Result<()> {
+    let chunk_len = chunk_len(header);
+    let mut hasher = Sha256::new();
+    let mut chunk = Vec::with_capacity(chunk_len);
+    let mut sent = 0u64;
+    let mut frames = 0u32;
+    for seq in 0u32.. {
+        read_chunk(file, chunk_len, &mut chunk)?;
+        if chunk.is_empty() {
+            break;
+        }
+        hasher.update(&chunk);
+        sent += chunk.len() as u64;
+        for (frame_seq, payload) in encode_chunk(header, seq, &chunk)? {
+            write_data(out, copy_header, &mut frames, frame_seq, &payload)?;
+        }
+    }
+    if sent != header.file_size {
+        return Err(Error::Integrity(format!(
+            "Файл изменился во время отправки: ожидалось {} байт, прочитано {}",
+            header.file_size, sent
+        )));
+    }
+
+    let digest: [u8; 32] = hasher.finalize().into();
+    send_trailer(out, frames, &digest)
+}
+
+/// Сколько кусков в файле.
+fn chunk_count(header: &Header) -> usize {
+    header.file_size.div_ceil(chunk_len(header) as u64) as usize
+}
+
+/// Отправляет одну копию из кусков `chunks` по порядку и трейлер с SHA-256 всего файла,
+/// посчитанной заранее. После каждого куска `progress` получает номер следующего.
+fn send_chunks<W: Write>(
+    out: &mut W,
+    file: &mut File,
+    header: &Header,
+    copy_header: &[u8],
+    chunks: impl IntoIterator<Item = usize>,
+    digest: &[u8; 32],
+    mut progress: impl FnMut(&mut W, usize) -> Result<()>,
+) -> Result<()> {
+    let chunk_len = chunk_len(header);
+    let mut chunk = Vec::with_capacity(chunk_len);
+    let mut frames = 0u32;
+    for seq in chunks {
+        file.seek(SeekFrom::Start(seq as u64 * chunk_len as u64)).map_err(Error::io("Ошибка чтения файла"))?;
+        read_chunk(file, chunk_len, &mut chunk)?;
+        if chunk.is_empty() {
+            return Err(Error::Integrity("Файл изменился во время отправки: он стал короче".to_string()));
+        }
+        for (frame_seq, payload) in encode_chunk(header, seq as u32, &chunk)? {
+            write_data(out, copy_header, &mut frames, frame_seq, &payload)?;
+        }
+        progress(out, seq + 1)?;
+    }
+    send_trailer(out, frames, digest)
+}
+
+fn send_trailer(out: &mut impl Write, frames: u32, digest: &[u8; 32]) -> Result<()> {
+    out.write_all(&encode_frame(FRAME_TRAILER, frames, &encode_trailer(digest)))
+        .map_err(Error::io("Ошибка отправки трейлера"))?;
+    eprintln!("Кадров данных: {}, SHA-256: {}", frames, hex(digest));
+    Ok(())
+}
+
+/// Открывает транспорт по адресу `port` (см. `Endpoint::parse`) и отправляет в него файл.
+pub fn send_file(port: &str, baud: u32, options: &SendOptions, file_path: &str) -> Result<()> {
+    let mut tx = Endpoint::parse(port)?.open_tx(baud)?;
+    send(&mut *tx, options, file_path)
+}
+
+/// Отправляет файл в открытый транспорт.
+pub fn send(tx: &mut dyn Tx, options: &SendOptions, file_path: &str) -> Result<()> {
+    transmit(tx, options, file_path, None)
+}
+
+/// Открывает транспорт по адресу `port` и отправляет заново части файла из отчёта о пропусках.
+pub fn resend_file(port: &str, baud: u32, options: &SendOptions, file_path: &str, gap: &GapReport) -> Result<()> {
+    let mut tx = Endpoint::parse(port)?.open_tx(baud)?;
+    resend(&mut *tx, options, file_path, gap)
+}
+
+/// Отправляет только части файла, которых не хватает приёмнику по отчёту `gap`, под
+/// идентификатором исходной передачи. Нарезка файла на части в `options` должна совпадать
+/// с отчётом — RS-блоки с тем же числом проверочных байтов или сегменты RaptorQ; перемежение,
+/// избыточность RaptorQ и число копий можно выбрать заново.
+pub fn resend(tx: &mut dyn Tx, options: &SendOptions, file_path: &str, gap: &GapReport) -> Result<()> {
+    transmit(tx, options, file_path, Some(gap))
+}
+
+/// Открывает транспорт по адресу `port` и продолжает передачу с контрольной точки `checkpoint`.
+pub fn resume_file(port: &str, baud: u32, checkpoint: &Path) -> Result<()> {
+    let mut tx = Endpoint::parse(port)?.open_tx(baud)?;
+    resume(&mut *tx, checkpoint)
+}
+
+/// Продолжает прерванную передачу с контрольной точки: тот же файл под тем же идентификатором
+/// и с теми же параметрами кодирования, начиная с копии и куска, до которых дошла отправка,
+/// с запасом `RESUME_OVERLAP` байт.
+pub fn resume(tx: &mut dyn Tx, checkpoint_path: &Path) -> Result<()> {
+    let checkpoint = Checkpoint::load(checkpoint_path)?;
+    let header = checkpoint.header.clone();
+    // Точка за концом передачи ничего бы не отправила, а затем удалилась как выполненная.
+    let chunks = chunk_count(&header);
+    if checkpoint.copy >= header.copies as u32 || checkpoint.next > chunks {
+        return Err(Error::Policy(format!(
+            "Некорректная контрольная точка {}: копия {} и кусок {} вне передачи из {} копий по {} кусков",
+            checkpoint_path.display(),
+            checkpoint.copy,
+            checkpoint.next,
+            header.copies,
+            chunks
+        )));
+    }
+    eprintln!("Открываем файл: {}", checkpoint.file.display());
+    let mut file = File::open(&checkpoint.file).map_err(Error::io("Не удалось открыть файл"))?;
+    let file_size = file.metadata().map_err(Error::io("Ошибка чтения файла"))?.len();
+    if file_size != header.file_size || file_digest(&mut file)? != checkpoint.sha256 {
+        return Err(Error::Integrity(format!(
+            "Файл {} изменился после прерванной отправки",
+            checkpoint.file.display()
+        )));
+    }
+    if fragment_len(tx.datagram_len())? != header.fragment_len {
+        return Err(Error::Policy(
+            "Транспорт не подходит к прерванной передаче: кадры режутся на датаграммы по-другому".to_string(),
+        ));
+    }
+
+    let overlap = RESUME_OVERLAP.div_ceil(chunk_len(&header) as u64) as usize;
+    let (first_copy, start) = (checkpoint.copy, checkpoint.next.saturating_sub(overlap));
+    eprintln!(
+        "Продолжаем передачу {:016x}: копия {} из {}, кусок {} из {}",
+        header.transfer_id,
+        first_copy + 1,
+        header.copies,
+        start,
+        chunks
+    );
+    let digest = checkpoint.sha256;
+    let mut tracker = Tracker::new(checkpoint_path, Checkpoint { next: start, ..checkpoint })?;
+    let header_frame = header.encode();
+    let mut out = writer(tx);
+    for copy in first_copy..header.copies as u32 {
+        let copy_header = encode_frame(FRAME_HEADER, copy, &header_frame);
+        out.write_all(&copy_header).map_err(Error::io("Ошибка отправки заголовка"))?;
+        let from = if copy == first_copy { start } else { 0 };
+        send_chunks(&mut out, &mut file, &header, &copy_header, from..chunks, &digest, |out, next| {
+            tracker.progress(out, copy, next)
+        })?;
+    }
+    out.flush().map_err(Error::io("Ошибка отправки данных"))?;
+    drop(out);
+    tracker.finish();
+    eprintln!("Данные успешно отправлены");
+    Ok(())
+}
+
+/// Транспорт с датаграммами получает каждый кадр отдельной записью и не режет его;
+/// группы RS дробятся на фрагменты по датаграмме, чтобы потеря датаграммы давала стирания.
+fn fragment_len(datagram_len: Option<usize>) -> Result<u16> {
+    let Some(len) = datagram_len else { return Ok(0) };
+    match len.checked_sub(FRAME_HEADER_LEN) {
+        Some(fragment) if fragment > 0 => Ok(fragment.min(u16::MAX as usize) as u16),
+        _ => Err(Error::Policy(format!("Датаграмма {} байт не вмещает даже служебные поля кадра", len))),
+    }
+}
+
+/// Кадры уходят в транспорт пачками, а в транспорт с датаграммами — по одному.
+fn writer(tx: &mut dyn Tx) -> Box<dyn Write + '_> {
+    match tx.datagram_len() {
+        None => Box::new(BufWriter::with_capacity(WRITE_BUFFER_LEN, tx)),
+        Some(_) => Box::new(tx),
+    }
+}
+
+/// SHA-256 файла, прочитанного с начала.
+fn file_digest(file: &mut File) -> Result<[u8; 32]> {
+    file.seek(SeekFrom::Start(0)).map_err(Error::io("Ошибка чтения файла"))?;
+    let mut hasher = Sha256::new();
+    io::copy(file, &mut hasher).map_err(Error::io("Ошибка чтения файла"))?;
+    Ok(hasher.finalize().into())
+}
+
+/// Проверяет, что отчёт о пропусках относится к этому файлу, и возвращает SHA-256 файла
+/// и номера кусков, в которых лежат недостающие части.
+fn check_gap(file: &mut File, header: &Header, gap: &GapReport) -> Result<([u8; 32], BTreeSet<usize>)> {
+    if header.file_size != gap.file_size {
+        return Err(Error::Policy(format!(
+            "Файл не совпадает с принятым: {} байт вместо {}",
+            header.file_size, gap.file_size
+        )));
+    }
+    let digest = file_digest(file)?;
+    if let Some(expected) = gap.digest
+        && digest[..GAP_DIGEST_LEN] != expected
+    {
+        return Err(Error::Integrity(format!(
+            "Файл не совпадает с принятым: SHA-256 начинается с {}, а не с {}",
+            hex(&digest[..GAP_DIGEST_LEN]),
+            hex(&expected)
+        )));
+    }
+    let per_chunk = match header.fec {
+        Fec::ReedSolomon => header.interleave as usize,
+        Fec::RaptorQ { .. } => 1,
+    };
+    let mut chunks = BTreeSet::new();
+    for unit in gap.units() {
+        if unit >= header.unit_count() {
+            return Err(Error::Policy(format!("Код пропусков не подходит к файлу: части {} в нём нет", unit)));
+        }
+        chunks.insert(unit / per_chunk);
+    }
+    Ok((digest, chunks))
+}
+
+fn transmit(tx: &mut dyn Tx, options: &SendOptions, file_path: &str, gap: Option<&GapReport>) -> Result<()> {
+    eprintln!("Открываем файл: {}", file_path);
+    let mut file = File::open(file_path).map_err(Error::io("Не удалось открыть файл"))?;
+    let file_size = file
+        .metadata()
+        .map_err(Error::io("Ошибка чтения файла"))?
+        .len();
+    eprintln!("Размер файла: {} байт", file_size);
+
+    let filename = std::path::Path::new(file_path)
+        .file_name()
+        .ok_or_else(|| Error::Policy("Неверный путь к файлу".to_string()))?
+        .to_str()
+        .ok_or_else(|| Error::Policy("Некорректное имя файла".to_string()))?;
+    eprintln!("Имя файла: {}", filename);
+
+    if let Some(gap) = gap {
+        let fits = match (gap.rs_bytes, options.fec) {
+            (Some(rs_bytes), FecMode::ReedSolomon) => rs_bytes == options.rs_bytes,
+            (None, FecMode::RaptorQ { .. }) => true,
+            _ => false,
+        };
+        if !fits {
+            return Err(Error::Policy(match gap.rs_bytes {
+                Some(rs_bytes) => format!("Код пропусков ждёт блоки RS с {} проверочными байтами", rs_bytes),
+                None => "Код пропусков ждёт сегменты RaptorQ".to_string(),
+            }));
+        }
+    }
+
+    let mut header = Header::new(options, file_size, filename, fragment_len(tx.datagram_len())?)?;
+    if header.filename != filename {
+        eprintln!("Имя файла обрезано до {} байт: {}", MAX_NAME_LEN, header.filename);
+    }
+    let resend = match gap {
+        None => None,
+        Some(gap) => {
+            let (digest, chunks) = check_gap(&mut file, &header, gap)?;
+            header.transfer_id = gap.transfer_id;
+            header.flags |= FLAG_RESEND;
+            eprintln!(
+                "Повторная отправка передачи {:016x}: частей {}, кадров на копию {}",
+                gap.transfer_id,
+                gap.units().count(),
+                chunks.len() * header.group_stride()
+            );
+            Some((digest, chunks))
+        }
+    };
+    // Контрольная точка нужна обычной отправке: SHA-256 считается заранее, чтобы
+    // продолжение могло проверить, что файл тот же.
+    let mut tracker = match (&options.checkpoint, &resend) {
+        (Some(path), None) => {
+            let checkpoint = Checkpoint {
+                file: std::fs::canonicalize(file_path).map_err(Error::io("Не удалось открыть файл"))?,
+                header: header.clone(),
+                sha256: file_digest(&mut file)?,
+                copy: 0,
+                next: 0,
+            };
+            eprintln!("Контрольная точка: {}", path.display());
+            Some(Tracker::new(path, checkpoint)?)
+        }
+        _ => None,
+    };
+    let header_frame = header.encode();
+
+    let mut out = writer(tx);
+
+    match options.fec {
+        FecMode::ReedSolomon => eprintln!(
+            "Отправка данных: версия протокола {}, RS {} байт, перемежение {}",
+            PROTOCOL_VERSION, options.rs_bytes, options.interleave
+        ),
+        FecMode::RaptorQ { repair_percent } => eprintln!(
+            "Отправка данных: версия протокола {}, RaptorQ, избыточность {}%",
+            PROTOCOL_VERSION, repair_percent
+        ),
+    }
+    if header.fragment_len > 0 {
+        eprintln!("Группы RS режутся на фрагменты по {} байт", header.fragment_len);
+    }
+    for copy in 0..header.copies {
+        if header.copies > 1 {
+            eprintln!("Отправка копии {} из {} (передача {:016x})", copy + 1, header.copies, header.transfer_id);
+        }
+        file.seek(SeekFrom::Start(0)).map_err(Error::io("Ошибка чтения файла"))?;
+        let copy_header = encode_frame(FRAME_HEADER, copy as u32, &header_frame);
+        out.write_all(&copy_header).map_err(Error::io("Ошибка отправки заголовка"))?;
+        match (&resend, &mut tracker) {
+            (Some((digest, chunks)), _) => {
+                send_chunks(&mut out, &mut file, &header, &copy_header, chunks.iter().copied(), digest, |_, _| Ok(()))?
+            }
+            (None, Some(tracker)) => {
+                let digest = tracker.checkpoint.sha256;
+                send_chunks(&mut out, &mut file, &header, &copy_header, 0..chunk_count(&header), &digest, |out, next| {
+                    tracker.progress(out, copy as u32, next)
+                })?
+            }
+            (None, None) => send_body(&mut out, &mut file, &header, &copy_header)?,
+        }
+    }
+    out.flush().map_err(Error::io("Ошибка отправки данных"))?;
+    if let Some(tracker) = tracker {
+        tracker.finish();
+    }
+    eprintln!("Данные успешно отправлены");
+
+    Ok(())
+}
@@ -0,0 +1,204 @@
+//! Транспорт: куда отправитель пишет поток и откуда приёмник его читает. Протокол работает
+//! поверх любого транспорта — последовательного порта, файла, stdin/stdout или канала в памяти.
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::path::PathBuf;
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+use serialport::SerialPort;
+
+use crate::error::{Error, Result};
+use crate::port::{self, SerialConfig};
+use crate::rfc2217::Rfc2217Port;
+use crate::udp::{UdpConfig, UdpRx, UdpTx};
+
+/// Сторона отправителя: транспорт только пишет, обратного канала у диода нет.
+pub trait Tx: Write + Send {
+    /// Размер датаграммы, если транспорт передаёт данные датаграммами. Тогда отправитель
+    /// пишет каждый кадр отдельным вызовом `write`, а кадры длиннее датаграммы не создаёт.
+    fn datagram_len(&self) -> Option<usize> {
+        None
+    }
+
+    /// Меняет скорость линии на ходу. Транспорты без скорости линии её не замечают.
+    fn set_baud_rate(&mut self, _baud: u32) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Сторона приёмника: транспорт только читает. Если данных нет дольше таймаута транспорта,
+/// чтение возвращает `io::ErrorKind::TimedOut`; `Ok(0)` означает, что источник закрыт.
+pub trait Rx: Read + Send {
+    /// Меняет скорость линии на ходу. Транспорты без скорости линии её не замечают.
+    fn set_baud_rate(&mut self, _baud: u32) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// Читает как `read` и отмечает в `marks` (не короче `buf`) байты, которые линия приняла
+    /// с ошибкой чётности или кадра. Транспорты без таких сведений ничего не отмечают.
+    fn read_marked(&mut self, buf: &mut [u8], marks: &mut [bool]) -> io::Result<usize> {
+        let n = self.read(buf)?;
+        marks[..n].fill(false);
+        Ok(n)
+    }
+}
+
+impl<T: Rx + ?Sized> Rx for Box<T> {
+    fn set_baud_rate(&mut self, baud: u32) -> io::Result<()> {
+        (**self).set_baud_rate(baud)
+    }
+
+    fn read_marked(&mut self, buf: &mut [u8], marks: &mut [bool]) -> io::Result<usize> {
+        (**self).read_marked(buf, marks)
+    }
+}
+
+impl Tx for Box<dyn SerialPort> {
+    fn set_baud_rate(&mut self, baud: u32) -> io::Result<()> {
+        SerialPort::set_baud_rate(self.as_mut(), baud).map_err(io::Error::from)
+    }
+}
+
+impl Rx for Box<dyn SerialPort> {
+    fn set_baud_rate(&mut self, baud: u32) -> io::Result<()> {
+        SerialPort::set_baud_rate(self.as_mut(), baud).map_err(io::Error::from)
+    }
+}
+
+impl Tx for File {}
+impl Rx for File {}
+impl Tx for io::Stdout {}
+impl Rx for io::Stdin {}
+
+/// Адрес транспорта из командной строки: `-` — stdin/stdout, `file:ПУТЬ` — файл,
+/// `udp://ХОСТ:ПОРТ?параметры` — UDP (см. `UdpConfig`), `rfc2217://ХОСТ:ПОРТ?parity=…` — порт на сервере
+/// портов по RFC 2217, всё остальное — последовательный порт (см. `SerialConfig`).
+#[derive(Debug, Clone, PartialEq)]
+pub enum Endpoint {
+    Serial(SerialConfig),
+    File(PathBuf),
+    Stdio,
+    Udp(UdpConfig),
+    /// Имя в `SerialConfig` — адрес сервера портов `ХОСТ:ПОРТ`.
+    Rfc2217(SerialConfig),
+}
+
+impl Endpoint {
+    pub fn parse(spec: &str) -> Result<Endpoint> {
+        Ok(if spec == "-" {
+            Endpoint::Stdio
+        } else if let Some(path) = spec.strip_prefix("file:") {
+            Endpoint::File(PathBuf::from(path))
+        } else if let Some(addr) = spec.strip_prefix("udp://") {
+            Endpoint::Udp(UdpConfig::parse(addr)?)
+        } else if let Some(addr) = spec.strip_prefix("rfc2217://") {
+            Endpoint::Rfc2217(SerialConfig::parse(addr)?)
+        } else {
+            Endpoint::Serial(SerialConfig::parse(spec)?)
+        })
+    }
+
+    pub fn open_tx(&self, baud: u32) -> Result<Box<dyn Tx>> {
+        Ok(match self {
+            Endpoint::Serial(config) => Box::new(port::open(config, baud)?),
+            Endpoint::File(path) => Box::new(File::create(path).map_err(Error::io("Ошибка создания файла"))?),
+            Endpoint::Stdio => Box::new(io::stdout()),
+            Endpoint::Udp(config) => Box::new(UdpTx::open(config, baud)?),
+            Endpoint::Rfc2217(config) => Box::new(Rfc2217Port::open(config, baud)?),
+        })
+    }
+
+    pub fn open_rx(&self, baud: u32) -> Result<Box<dyn Rx>> {
+        Ok(match self {
+            Endpoint::Serial(config) => port::open_rx(config, baud)?,
+            Endpoint::File(path) => Box::new(File::open(path).map_err(Error::io("Не удалось открыть файл"))?),
+            Endpoint::Stdio => Box::new(io::stdin()),
+            Endpoint::Udp(config) => Box::new(UdpRx::open(config)?),
+            Endpoint::Rfc2217(config) => Box::new(Rfc2217Port::open(config, baud)?),
+        })
+    }
+}
+
+impl fmt::Display for Endpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Endpoint::Serial(config) => write!(f, "{}", config),
+            Endpoint::File(path) => write!(f, "file:{}", path.display()),
+            Endpoint::Stdio => write!(f, "-"),
+            Endpoint::Udp(config) => write!(f, "udp://{}", config.addr),
+            Endpoint::Rfc2217(config) => write!(f, "rfc2217://{}", config),
+        }
+    }
+}
+
+struct PipeState {
+    buf: VecDeque<u8>,
+    closed: bool,
+}
+
+type PipeShared = Arc<(Mutex<PipeState>, Condvar)>;
+
+/// Пишущий конец канала в памяти. Когда он уничтожен, читающий конец получает конец потока.
+pub struct PipeTx {
+    shared: PipeShared,
+}
+
+/// Читающий конец канала в памяти: ведёт себя как порт с таймаутом чтения.
+pub struct PipeRx {
+    shared: PipeShared,
+    timeout: Duration,
+}
+
+/// Канал в памяти между отправителем и приёмником в одном процессе.
+pub fn pipe(timeout: Duration) -> (PipeTx, PipeRx) {
+    let shared = Arc::new((Mutex::new(PipeState { buf: VecDeque::new(), closed: false }), Condvar::new()));
+    (PipeTx { shared: shared.clone() }, PipeRx { shared, timeout })
+}
+
+impl Write for PipeTx {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let (state, ready) = &*self.shared;
+        state.lock().unwrap().buf.extend(buf);
+        ready.notify_all();
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Drop for PipeTx {
+    fn drop(&mut self) {
+        let (state, ready) = &*self.shared;
+        state.lock().unwrap().closed = true;
+        ready.notify_all();
+    }
+}
+
+impl Read for PipeRx {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let (state, ready) = &*self.shared;
+        let deadline = Instant::now() + self.timeout;
+        let mut state = state.lock().unwrap();
+        while state.buf.is_empty() && !state.closed {
+            let now = Instant::now();
+            if now >= deadline {
+                return Err(io::ErrorKind::TimedOut.into());
+            }
+            state = ready.wait_timeout(state, deadline - now).unwrap().0;
+        }
+        let n = buf.len().min(state.buf.len());
+        for (dst, src) in buf.iter_mut().zip(state.buf.drain(..n)) {
+            *dst = src;
+        }
+        Ok(n)
+    }
+}
+
+impl Tx for PipeTx {}
+impl Rx for PipeRx {}
@@ -0,0 +1,245 @@
+//! Транспорт UDP для оптических и Ethernet-диодов, которые пропускают только датаграммы в одну сторону.
+//!
+//! Каждая датаграмма содержит только целые кадры, поэтому потерянная датаграмма стоит целых кадров,
+//! а не рвёт поток посередине кадра. Группы RS при этом режутся на фрагменты по датаграмме
+//! (см. `Header::fragment_len`), и приёмник восстанавливает потерянный фрагмент как стирания.
+
+use std::io::{self, Read, Write};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, ToSocketAddrs, UdpSocket};
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::error::{Error, Result};
+use crate::fec::RS_CODEWORD_LEN;
+use crate::port::PORT_TIMEOUT;
+use crate::protocol::FRAME_HEADER_LEN;
+use crate::transport::{Rx, Tx};
+
+/// Размер датаграммы по умолчанию: помещается в кадр Ethernet с MTU 1500 без IP-фрагментации.
+pub const DEFAULT_DATAGRAM_LEN: usize = 1472;
+/// Наименьшая датаграмма, в которую помещается кадр заголовка.
+pub const MIN_DATAGRAM_LEN: usize = FRAME_HEADER_LEN + RS_CODEWORD_LEN;
+/// Наибольшая полезная нагрузка UDP поверх IPv4.
+pub const MAX_DATAGRAM_LEN: usize = 65507;
+
+/// Параметры UDP из адреса вида `udp://ХОСТ:ПОРТ?size=1472&rate=100000000&iface=АДРЕС&ttl=1`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct UdpConfig {
+    /// Куда отправляет отправитель; для приёмника — адрес, который он слушает, или группа multicast.
+    pub addr: SocketAddr,
+    /// Размер датаграммы, байт.
+    pub datagram_len: usize,
+    /// Скорость отправки, бит/с; без неё отправитель берёт скорость линии `baud`.
+    pub rate: Option<u64>,
+    /// Адрес интерфейса IPv4: с него отправляет отправитель, на нём приёмник присоединяется к группе multicast.
+    pub iface: Ipv4Addr,
+    /// TTL датаграмм multicast.
+    pub ttl: u32,
+}
+
+impl UdpConfig {
+    /// Разбирает адрес без префикса `udp://`.
+    pub fn parse(spec: &str) -> Result<Self> {
+        let (addr, query) = spec.split_once('?').unwrap_or((spec, ""));
+        let addr = addr
+            .to_socket_addrs()
+            .map_err(|e| Error::Policy(format!("Некорректный адрес UDP {}: {}", addr, e)))?
+            .next()
+            .ok_or_else(|| Error::Policy(format!("Адрес UDP не найден: {}", addr)))?;
+        let mut config =
+            UdpConfig { addr, datagram_len: DEFAULT_DATAGRAM_LEN, rate: None, iface: Ipv4Addr::UNSPECIFIED, ttl: 1 };
+        for param in query.split('&').filter(|p| !p.is_empty()) {
+            let (key, value) = param.split_once('=').unwrap_or((param, ""));
+            let invalid = || Error::Policy(format!("Некорректный параметр UDP: {}", param));
+            match key {
+                "size" => config.datagram_len = value.parse().map_err(|_| invalid())?,
+                "rate" => config.rate = Some(value.parse().map_err(|_| invalid())?).filter(|&rate| rate > 0),
+                "iface" => config.iface = value.parse().map_err(|_| invalid())?,
+                "ttl" => config.ttl = value.parse().map_err(|_| invalid())?,
+                _ => return Err(Error::Policy(format!("Неизвестный параметр UDP: {}", key))),
+            }
+        }
+        if !(MIN_DATAGRAM_LEN..=MAX_DATAGRAM_LEN).contains(&config.datagram_len) {
+            return Err(Error::Policy(format!(
+                "Размер датаграммы должен быть от {} до {} байт",
+                MIN_DATAGRAM_LEN, MAX_DATAGRAM_LEN
+            )));
+        }
+        Ok(config)
+    }
+}
+
+/// Отправляющий сокет: собирает кадры в датаграммы и выдерживает заданную скорость.
+pub struct UdpTx {
+    /// Сокет не соединяется с адресатом: иначе ICMP «порт недоступен» с той стороны
+    /// прерывал бы отправку, а отправитель диода не должен зависеть от получателя.
+    socket: UdpSocket,
+    addr: SocketAddr,
+    datagram_len: usize,
+    /// Бит в секунду.
+    rate: u64,
+    pending: Vec<u8>,
+    next_send: Instant,
+}
+
+impl UdpTx {
+    pub fn open(config: &UdpConfig, baud: u32) -> Result<Self> {
+        let local: SocketAddr = match config.addr {
+            SocketAddr::V4(_) => (config.iface, 0).into(),
+            SocketAddr::V6(_) => (Ipv6Addr::UNSPECIFIED, 0).into(),
+        };
+        let socket = UdpSocket::bind(local).map_err(Error::io("Не удалось открыть сокет UDP"))?;
+        if config.addr.ip().is_multicast() {
+            let ttl = match config.addr {
+                SocketAddr::V4(_) => socket.set_multicast_ttl_v4(config.ttl),
+                SocketAddr::V6(_) => Ok(()),
+            };
+            ttl.map_err(Error::io("Не удалось настроить multicast"))?;
+        }
+        let rate = config.rate.unwrap_or(baud as u64).max(1);
+        eprintln!("UDP: отправка на {}, датаграмма {} байт, скорость {} бит/с", config.addr, config.datagram_len, rate);
+        Ok(UdpTx {
+            socket,
+            addr: config.addr,
+            datagram_len: config.datagram_len,
+            rate,
+            pending: Vec::with_capacity(config.datagram_len),
+            next_send: Instant::now(),
+        })
+    }
+
+    /// Отправляет накопленные кадры одной датаграммой, не быстрее заданной скорости.
+    fn send_pending(&mut self) -> io::Result<()> {
+        if self.pending.is_empty() {
+            return Ok(());
+        }
+        let now = Instant::now();
+        if self.next_send > now {
+            thread::sleep(self.next_send - now);
+        }
+        let bits = self.pending.len() as u64 * 8;
+        self.next_send = self.next_send.max(now) + Duration::from_nanos(bits * 1_000_000_000 / self.rate);
+        self.socket.send_to(&self.pending, self.addr)?;
+        self.pending.clear();
+        Ok(())
+    }
+}
+
+impl Write for UdpTx {
+    /// Каждый вызов должен нести целые кадры: они не разрезаются между датаграммами.
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if buf.len() > self.datagram_len {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("Кадр {} байт не помещается в датаграмму {} байт", buf.len(), self.datagram_len),
+            ));
+        }
+        if self.pending.len() + buf.len() > self.datagram_len {
+            self.send_pending()?;
+        }
+        self.pending.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.send_pending()
+    }
+}
+
+impl Tx for UdpTx {
+    fn datagram_len(&self) -> Option<usize> {
+        Some(self.datagram_len)
+    }
+}
+
+/// Как часто поток чтения сокета проверяет, не закрыт ли приёмник.
+const SOCKET_POLL: Duration = Duration::from_millis(200);
+
+/// Принимающий сокет: отдаёт датаграммы как поток байтов. Сокет читает отдельный поток,
+/// чтобы датаграммы не терялись в буфере ядра, пока приёмник занят декодированием.
+pub struct UdpRx {
+    datagrams: mpsc::Receiver<io::Result<Vec<u8>>>,
+    datagram: Vec<u8>,
+    pos: usize,
+}
+
+impl UdpRx {
+    /// Слушает адрес из `config`; если это группа multicast, слушает её порт на всех адресах
+    /// и присоединяется к группе.
+    pub fn open(config: &UdpConfig) -> Result<Self> {
+        let socket = match config.addr.ip() {
+            IpAddr::V4(group) if group.is_multicast() => {
+                let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, config.addr.port()))
+                    .map_err(Error::io("Не удалось открыть сокет UDP"))?;
+                socket
+                    .join_multicast_v4(&group, &config.iface)
+                    .map_err(Error::io("Не удалось присоединиться к группе multicast"))?;
+                socket
+            }
+            IpAddr::V6(group) if group.is_multicast() => {
+                let socket = UdpSocket::bind((Ipv6Addr::UNSPECIFIED, config.addr.port()))
+                    .map_err(Error::io("Не удалось открыть сокет UDP"))?;
+                socket
+                    .join_multicast_v6(&group, 0)
+                    .map_err(Error::io("Не удалось присоединиться к группе multicast"))?;
+                socket
+            }
+            _ => UdpSocket::bind(config.addr).map_err(Error::io("Не удалось открыть сокет UDP"))?,
+        };
+        socket
+            .set_read_timeout(Some(SOCKET_POLL))
+            .map_err(Error::io("Не удалось открыть сокет UDP"))?;
+        eprintln!("UDP: приём на {}", config.addr);
+        let (sender, datagrams) = mpsc::channel();
+        thread::spawn(move || read_socket(socket, sender));
+        Ok(UdpRx { datagrams, datagram: Vec::new(), pos: 0 })
+    }
+}
+
+/// Читает датаграммы, пока приёмник не закрыт. Пустые датаграммы пропускает: для читающего
+/// они выглядели бы как конец потока.
+fn read_socket(socket: UdpSocket, sender: mpsc::Sender<io::Result<Vec<u8>>>) {
+    let mut buf = vec![0; MAX_DATAGRAM_LEN];
+    loop {
+        let datagram = match socket.recv(&mut buf) {
+            Ok(0) => continue,
+            Ok(n) => buf[..n].to_vec(),
+            Err(e) if matches!(e.kind(), io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut) => {
+                // Таймаут нужен только, чтобы заметить закрытие приёмника.
+                if sender.send(Ok(Vec::new())).is_err() {
+                    return;
+                }
+                continue;
+            }
+            Err(e) => {
+                let _ = sender.send(Err(e));
+                return;
+            }
+        };
+        if sender.send(Ok(datagram)).is_err() {
+            return;
+        }
+    }
+}
+
+impl Read for UdpRx {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let deadline = Instant::now() + PORT_TIMEOUT;
+        while self.pos == self.datagram.len() {
+            let wait = deadline.saturating_duration_since(Instant::now());
+            self.datagram = match self.datagrams.recv_timeout(wait) {
+                Ok(datagram) => datagram?,
+                Err(mpsc::RecvTimeoutError::Timeout) => return Err(io::ErrorKind::TimedOut.into()),
+                Err(mpsc::RecvTimeoutError::Disconnected) => return Ok(0),
+            };
+            self.pos = 0;
+        }
+        let n = buf.len().min(self.datagram.len() - self.pos);
+        buf[..n].copy_from_slice(&self.datagram[self.pos..self.pos + n]);
+        self.pos += n;
+        Ok(n)
+    }
+}
+
+impl Rx for UdpRx {}
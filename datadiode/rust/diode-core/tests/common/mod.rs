@@ -0,0 +1,126 @@
+//! Общее для тестов крейта. Каждый тест берёт только своё, остальное для него не используется.
+#![allow(dead_code)]
+
+use std::fs;
+use std::io::{self, Write};
+use std::path::PathBuf;
+
+use diode_core::protocol::{Fec, Header, FRAME_HEADER_LEN};
+use diode_core::transport::PipeTx;
+use diode_core::{SendOptions, Tx, HEADER_REPEAT_FRAMES};
+
+/// Временная директория теста, удаляется вместе со всем содержимым. Имя тестового
+/// файла в пути разводит одноимённые директории разных тестов.
+pub struct TempDir(pub PathBuf);
+
+impl TempDir {
+    pub fn new(name: &str) -> Self {
+        let path = std::env::temp_dir().join(format!("diode-{}-{}-{}", env!("CARGO_CRATE_NAME"), name, std::process::id()));
+        let _ = fs::remove_dir_all(&path);
+        fs::create_dir_all(&path).unwrap();
+        TempDir(path)
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+/// Инвертирует байты потока с заданными смещениями.
+pub struct CorruptTx {
+    pub inner: PipeTx,
+    pub pos: u64,
+    pub flips: Vec<u64>,
+}
+
+impl CorruptTx {
+    pub fn new(inner: PipeTx, flips: Vec<u64>) -> Self {
+        CorruptTx { inner, pos: 0, flips }
+    }
+}
+
+impl Write for CorruptTx {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut data = buf.to_vec();
+        for &offset in &self.flips {
+            if (self.pos..self.pos + buf.len() as u64).contains(&offset) {
+                data[(offset - self.pos) as usize] ^= 0xFF;
+            }
+        }
+        self.pos += buf.len() as u64;
+        self.inner.write(&data)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl Tx for CorruptTx {}
+
+/// Где в потоке первой копии лежат кадры и байты кодовых слов. Считается по заголовку
+/// с параметрами отправки теста: с повторами заголовка, перемежением и коротким последним блоком.
+/// Только RS, группа целиком в одном кадре.
+pub struct Layout {
+    pub header: Header,
+}
+
+impl Layout {
+    pub fn new(options: &SendOptions, file_size: u64) -> Self {
+        let header = Header::new(options, file_size, "data.bin", 0).unwrap();
+        assert_eq!(header.fec, Fec::ReedSolomon, "раскладка считается только для RS");
+        Layout { header }
+    }
+
+    fn header_frame_len(&self) -> u64 {
+        (FRAME_HEADER_LEN + self.header.encode().len()) as u64
+    }
+
+    /// Конец `frames` кадров данных: перед повтором заголовка, если он идёт следом.
+    pub fn data_end(&self, frames: usize) -> u64 {
+        let repeats = (frames.saturating_sub(1) / HEADER_REPEAT_FRAMES as usize) as u64;
+        let payload: usize = (0..frames).map(|group| self.header.group_payload_len(group)).sum();
+        self.header_frame_len() * (1 + repeats) + (frames * FRAME_HEADER_LEN + payload) as u64
+    }
+
+    /// Начало кадра данных `frame`.
+    pub fn frame_start(&self, frame: usize) -> u64 {
+        let repeat = frame > 0 && frame.is_multiple_of(HEADER_REPEAT_FRAMES as usize);
+        self.data_end(frame) + if repeat { self.header_frame_len() } else { 0 }
+    }
+
+    /// Начало трейлера после всех кадров данных.
+    pub fn trailer_start(&self) -> u64 {
+        self.frame_start(self.header.group_count())
+    }
+
+    /// Смещение байта `byte` кодового слова блока `block`, если его группа идёт кадром `frame`.
+    pub fn block_offset_in_frame(&self, frame: usize, block: usize, byte: usize) -> u64 {
+        let group = block / self.header.interleave as usize;
+        let lens: Vec<usize> = self.header.group_blocks(group).map(|i| self.header.codeword_len(i)).collect();
+        let within = block - group * self.header.interleave as usize;
+        assert!(byte < lens[within]);
+        let before: usize = (0..byte).map(|pos| lens.iter().filter(|&&len| len > pos).count()).sum();
+        let beside = lens[..within].iter().filter(|&&len| len > byte).count();
+        self.frame_start(frame) + (FRAME_HEADER_LEN + before + beside) as u64
+    }
+
+    /// Смещение байта `byte` кодового слова блока `block` при отправке файла целиком.
+    pub fn block_offset(&self, block: usize, byte: usize) -> u64 {
+        self.block_offset_in_frame(block / self.header.interleave as usize, block, byte)
+    }
+
+    /// На три ошибки больше, чем исправляет RS, вразброс по кодовому слову блока `block` из кадра `frame`.
+    pub fn ruin_block_in_frame(&self, frame: usize, block: usize) -> Vec<u64> {
+        let errors = self.header.rs_bytes as usize / 2 + 3;
+        let step = self.header.codeword_len(block) / errors;
+        (0..errors).map(|i| self.block_offset_in_frame(frame, block, i * step)).collect()
+    }
+
+    /// Блок `block`, который не исправить, при отправке файла целиком.
+    pub fn ruin_block(&self, block: usize) -> Vec<u64> {
+        self.ruin_block_in_frame(block / self.header.interleave as usize, block)
+    }
+}
@@ -0,0 +1,78 @@
+//! Статистика коррекции: ошибки, внесённые в известные байты потока, видны в итоге приёма.
+
+mod common;
+
+use std::fs;
+use std::thread;
+
+use diode_core::port::PORT_TIMEOUT;
+use diode_core::transport::pipe;
+use diode_core::{send, DecodeStats, Event, Receiver, SendOptions};
+use common::{CorruptTx, Layout, TempDir};
+
+const FILE_LEN: u64 = 2000;
+
+fn receive(name: &str, options: SendOptions, flips: Vec<u64>) -> DecodeStats {
+    let dir = TempDir::new(name);
+    let path = dir.0.join("data.bin");
+    fs::write(&path, (0..FILE_LEN as u32).map(|i| (i * 7) as u8).collect::<Vec<_>>()).unwrap();
+    let (pipe_tx, pipe_rx) = pipe(PORT_TIMEOUT);
+    let mut tx = CorruptTx::new(pipe_tx, flips);
+    let sender = thread::spawn(move || send(&mut tx, &options, path.to_str().unwrap()));
+    let mut receiver = Receiver::new(Box::new(pipe_rx), &dir.0.join("out").to_string_lossy());
+    let event = loop {
+        if let Some(event) = receiver.poll().unwrap() {
+            break event;
+        }
+    };
+    sender.join().unwrap().unwrap();
+    match event {
+        Event::Received(file) => file.stats,
+        event => panic!("{:?}", event),
+    }
+}
+
+#[test]
+fn clean_transfer_has_nothing_corrected() {
+    let stats = receive("clean", SendOptions::default(), Vec::new());
+    assert_eq!(stats.units, 9);
+    assert_eq!((stats.corrected_symbols, stats.corrected_blocks, stats.uncorrectable), (0, 0, 0));
+    assert_eq!(stats.worst_block, None);
+    assert_eq!(stats.histogram[0], 9);
+    assert_eq!(stats.correctable, 5);
+}
+
+#[test]
+fn corrections_are_counted_per_block() {
+    let layout = Layout::new(&SendOptions::default(), FILE_LEN);
+    let flips = vec![layout.block_offset(0, 3), layout.block_offset(0, 100), layout.block_offset(0, 200), layout.block_offset(2, 50)];
+    let stats = receive("corrected", SendOptions::default(), flips);
+    assert_eq!(stats.corrected_symbols, 4);
+    assert_eq!(stats.corrected_blocks, 2);
+    assert_eq!(stats.worst_block, Some((0, 3)));
+    assert_eq!((stats.histogram[0], stats.histogram[1], stats.histogram[3]), (7, 1, 1));
+    assert_eq!(stats.uncorrectable, 0);
+    assert!(stats.to_string().contains("худший блок 0: 3 исправлений из 5"), "{}", stats);
+}
+
+#[test]
+fn uncorrectable_copies_are_counted() {
+    let options = SendOptions { repeat: 2, ..SendOptions::default() };
+    let flips = Layout::new(&options, FILE_LEN).ruin_block(1);
+    let stats = receive("uncorrectable", options, flips);
+    // Вторая копия пришла чистой и заменила всё, что исправлялось в первой.
+    assert_eq!(stats.uncorrectable, 1);
+    assert_eq!(stats.corrected_symbols, 0);
+    assert_eq!(stats.units, 9);
+}
+
+#[test]
+fn corrections_follow_the_interleaved_layout() {
+    // Перемежение 4: блоки 4–7 во втором кадре, короткий последний блок 8 один в третьем.
+    let options = SendOptions { interleave: 4, ..SendOptions::default() };
+    let layout = Layout::new(&options, FILE_LEN);
+    let flips = vec![layout.block_offset(6, 0), layout.block_offset(6, 200), layout.block_offset(8, 45)];
+    let stats = receive("interleaved", options, flips);
+    assert_eq!(stats.worst_block, Some((6, 2)));
+    assert_eq!((stats.corrected_symbols, stats.corrected_blocks), (3, 2));
+}
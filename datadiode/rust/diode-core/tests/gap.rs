@@ -0,0 +1,188 @@
+//! Отчёт о пропусках: приёмник печатает код, отправитель по нему шлёт только недостающие
+//! части, приёмник дописывает их в частичный файл.
+
+mod common;
+
+use std::fs;
+use std::path::Path;
+use std::thread;
+
+use diode_core::port::PORT_TIMEOUT;
+use diode_core::transport::pipe;
+use diode_core::{resend, send, Error, Event, FecMode, GapReport, ReceivedFile, Receiver, SendOptions, Tx};
+use common::{CorruptTx, Layout, TempDir};
+
+fn content() -> Vec<u8> {
+    (0..5000u32).map(|i| (i * 13 + 5) as u8).collect()
+}
+
+fn layout() -> Layout {
+    Layout::new(&SendOptions::default(), content().len() as u64)
+}
+
+/// Прогоняет передачу через канал и возвращает событие приёмника, если оно было.
+fn transfer(
+    out: &Path,
+    flips: Vec<u64>,
+    run: impl FnOnce(&mut dyn Tx) -> diode_core::Result<()> + Send + 'static,
+) -> Option<Event> {
+    let (pipe_tx, pipe_rx) = pipe(PORT_TIMEOUT);
+    let mut tx = CorruptTx::new(pipe_tx, flips);
+    let sender = thread::spawn(move || run(&mut tx));
+    let mut receiver = Receiver::new(Box::new(pipe_rx), &out.to_string_lossy());
+    receiver.set_salvage(true);
+    let event = loop {
+        match receiver.poll() {
+            Ok(Some(event)) => break Some(event),
+            Ok(None) => {}
+            Err(Error::Closed) => break None,
+            Err(e) => panic!("{}", e),
+        }
+    };
+    sender.join().unwrap().unwrap();
+    event
+}
+
+fn received(event: Option<Event>) -> ReceivedFile {
+    match event {
+        Some(Event::Received(file)) => file,
+        event => panic!("передача не принята: {:?}", event),
+    }
+}
+
+fn names(dir: &Path) -> Vec<String> {
+    let mut names: Vec<String> = fs::read_dir(dir).unwrap().map(|e| e.unwrap().file_name().to_string_lossy().into_owned()).collect();
+    names.sort();
+    names
+}
+
+#[test]
+fn code_survives_hand_copying() {
+    let gap = GapReport {
+        transfer_id: 0x0123_4567_89AB_CDEF,
+        file_size: 10_000_000,
+        rs_bytes: Some(32),
+        digest: Some([1, 2, 3, 4, 5, 6, 7, 8]),
+        missing: vec![3..=3, 10..=14, 40_000..=40_001],
+    };
+    let code = gap.encode();
+    assert!(code.starts_with("DDGAP-"), "{}", code);
+    assert!(code.len() < 80, "{}", code);
+    assert_eq!(GapReport::decode(&code).unwrap(), gap);
+
+    // Строчные буквы, пробелы вместо дефисов, O вместо 0 и l вместо 1.
+    let sloppy = code.to_lowercase().replace('-', " ").replace('0', "o").replace('1', "l");
+    assert_eq!(GapReport::decode(&sloppy).unwrap(), gap);
+
+    let body = code.len() - 3;
+    let mut typo = code.clone().into_bytes();
+    typo[body] = if typo[body] == b'A' { b'B' } else { b'A' };
+    assert!(matches!(GapReport::decode(std::str::from_utf8(&typo).unwrap()), Err(Error::Policy(_))));
+
+    let raptorq = GapReport { rs_bytes: None, digest: None, missing: Vec::new(), ..gap.clone() };
+    assert_eq!(GapReport::decode(&raptorq.encode()).unwrap(), raptorq);
+
+    // RS без проверочных байтов — не RaptorQ: повторная отправка должна прийти блоками.
+    let rs_zero = GapReport { rs_bytes: Some(0), ..gap };
+    assert_eq!(GapReport::decode(&rs_zero.encode()).unwrap(), rs_zero);
+}
+
+#[test]
+fn resend_patches_the_partial_file() {
+    let dir = TempDir::new("patch");
+    let source = dir.0.join("data.bin");
+    fs::write(&source, content()).unwrap();
+    let out = dir.0.join("out");
+
+    let path = source.to_str().unwrap().to_string();
+    let flips = [2, 3, 15].into_iter().flat_map(|block| layout().ruin_block(block)).collect();
+    let first = received(transfer(&out, flips, move |tx| {
+        send(tx, &SendOptions::default(), &path)
+    }));
+    let damage = first.damage.expect("файл должен быть частичным");
+    assert_eq!(damage.gap.missing, [2..=3, 15..=15]);
+    assert!(Path::new(&damage.path).exists());
+    let code = damage.gap.encode();
+
+    // Повтор с другим перемежением: нарезка на блоки от него не зависит.
+    let gap = GapReport::decode(&code).unwrap();
+    let path = source.to_str().unwrap().to_string();
+    let options = SendOptions { interleave: 4, ..SendOptions::default() };
+    let patched = received(transfer(&out, Vec::new(), move |tx| resend(tx, &options, &path, &gap)));
+    assert!(patched.damage.is_none());
+    assert_eq!(fs::read(&patched.path).unwrap(), content());
+    let names = names(&out);
+    assert_eq!(names.len(), 1, "{:?}", names);
+    assert!(names[0].starts_with("decoded_"), "{:?}", names);
+}
+
+#[test]
+fn damaged_resend_keeps_the_file_partial() {
+    let dir = TempDir::new("again");
+    let source = dir.0.join("data.bin");
+    fs::write(&source, content()).unwrap();
+    let out = dir.0.join("out");
+
+    let path = source.to_str().unwrap().to_string();
+    let flips = [1, 7].into_iter().flat_map(|block| layout().ruin_block(block)).collect();
+    let first = received(transfer(&out, flips, move |tx| {
+        send(tx, &SendOptions::default(), &path)
+    }));
+    let gap = first.damage.unwrap().gap;
+
+    // В повторе идут только блоки 1 и 7; второй кадр повтора снова испорчен.
+    let path = source.to_str().unwrap().to_string();
+    let flips = layout().ruin_block_in_frame(1, 7);
+    let second = received(transfer(&out, flips, move |tx| resend(tx, &SendOptions::default(), &path, &gap)));
+    let damage = second.damage.expect("файл должен остаться частичным");
+    assert_eq!(damage.gap.missing, [7..=7]);
+    assert_eq!(second.path, first.path);
+}
+
+#[test]
+fn resend_checks_the_file() {
+    let dir = TempDir::new("mismatch");
+    let source = dir.0.join("data.bin");
+    fs::write(&source, content()).unwrap();
+    let gap = GapReport {
+        transfer_id: 1,
+        file_size: content().len() as u64,
+        rs_bytes: Some(10),
+        digest: Some([0; 8]),
+        missing: vec![0..=0],
+    };
+    let (mut tx, _rx) = pipe(PORT_TIMEOUT);
+    let result = resend(&mut tx, &SendOptions::default(), source.to_str().unwrap(), &gap);
+    assert!(matches!(result, Err(Error::Integrity(_))), "{:?}", result);
+
+    let gap = GapReport { digest: None, missing: vec![1000..=1000], ..gap };
+    let result = resend(&mut tx, &SendOptions::default(), source.to_str().unwrap(), &gap);
+    assert!(matches!(result, Err(Error::Policy(_))), "{:?}", result);
+
+    // Нарезка из параметров должна совпадать с отчётом, а не подменяться им.
+    let gap = GapReport { missing: vec![0..=0], ..gap };
+    let raptorq = SendOptions { fec: FecMode::RaptorQ { repair_percent: 10 }, ..SendOptions::default() };
+    for options in [SendOptions { rs_bytes: 12, ..SendOptions::default() }, raptorq] {
+        let result = resend(&mut tx, &options, source.to_str().unwrap(), &gap);
+        assert!(matches!(result, Err(Error::Policy(_))), "{:?}", result);
+    }
+    let result = resend(&mut tx, &SendOptions::default(), source.to_str().unwrap(), &GapReport { rs_bytes: None, ..gap });
+    assert!(matches!(result, Err(Error::Policy(_))), "{:?}", result);
+}
+
+#[test]
+fn resend_without_partial_file_is_ignored() {
+    let dir = TempDir::new("orphan");
+    let source = dir.0.join("data.bin");
+    fs::write(&source, content()).unwrap();
+    let gap = GapReport {
+        transfer_id: 7,
+        file_size: content().len() as u64,
+        rs_bytes: None,
+        digest: None,
+        missing: vec![0..=0],
+    };
+    let path = source.to_str().unwrap().to_string();
+    let options = SendOptions { fec: FecMode::RaptorQ { repair_percent: 10 }, ..SendOptions::default() };
+    assert!(transfer(&dir.0.join("out"), Vec::new(), move |tx| resend(tx, &options, &path, &gap)).is_none());
+}
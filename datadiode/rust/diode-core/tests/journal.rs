@@ -0,0 +1,228 @@
+//! Журнал приёма: приёмник, перезапущенный посреди передачи, продолжает её с принятого.
+
+mod common;
+
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use std::time::Duration;
+
+use diode_core::transport::pipe;
+use diode_core::{send, Error, Event, Receiver, SendOptions, HEADER_REPEAT_FRAMES};
+use common::{Layout, TempDir};
+
+const TIMEOUT: Duration = Duration::from_millis(200);
+
+fn content(len: usize) -> Vec<u8> {
+    (0..len as u32).map(|i| (i * 17 + i / 300) as u8).collect()
+}
+
+/// Поток отправителя, записанный в файл.
+fn stream(dir: &TempDir, data: &[u8], options: &SendOptions) -> Vec<u8> {
+    let source = dir.0.join("data.bin");
+    fs::write(&source, data).unwrap();
+    let out = dir.0.join("stream.bin");
+    send(&mut fs::File::create(&out).unwrap(), options, source.to_str().unwrap()).unwrap();
+    fs::read(out).unwrap()
+}
+
+/// Первый запуск приёмника: получает `prefix`, после чего поток замирает. Возвращает
+/// приёмник после события о прерванной передаче.
+fn first_run(prefix: &[u8], out: &Path) -> Receiver {
+    let (mut tx, rx) = pipe(TIMEOUT);
+    tx.write_all(prefix).unwrap();
+    std::mem::forget(tx);
+    let mut receiver = Receiver::new(Box::new(rx), &out.to_string_lossy());
+    loop {
+        match receiver.poll().unwrap() {
+            Some(Event::Suspended(_)) => return receiver,
+            Some(event) => panic!("передача завершилась раньше времени: {:?}", event),
+            None => {}
+        }
+    }
+}
+
+/// Второй запуск: получает `rest` и возвращает первое событие.
+fn second_run(rest: &[u8], out: &Path) -> Event {
+    let (mut tx, rx) = pipe(TIMEOUT);
+    tx.write_all(rest).unwrap();
+    drop(tx);
+    let mut receiver = Receiver::new(Box::new(rx), &out.to_string_lossy());
+    loop {
+        match receiver.poll() {
+            Ok(Some(event)) => return event,
+            Ok(None) => {}
+            Err(Error::Closed) => panic!("поток закончился без события"),
+            Err(e) => panic!("{}", e),
+        }
+    }
+}
+
+/// Конец кадров данных перед первым повтором заголовка: первые блоки есть только до него.
+fn before_repeated_header(data: &[u8]) -> usize {
+    Layout::new(&SendOptions::default(), data.len() as u64).data_end(HEADER_REPEAT_FRAMES as usize) as usize
+}
+
+fn names(dir: &Path) -> Vec<String> {
+    let mut names: Vec<String> = fs::read_dir(dir).unwrap().map(|e| e.unwrap().file_name().to_string_lossy().into_owned()).collect();
+    names.sort();
+    names
+}
+
+#[test]
+fn restarted_receiver_continues_at_repeated_header() {
+    let dir = TempDir::new("repeated");
+    let data = content(200_000);
+    let stream = stream(&dir, &data, &SendOptions::default());
+    let out = dir.0.join("out");
+
+    // Обрыв ровно перед повтором заголовка: первые блоки есть только у первого запуска.
+    let cut = before_repeated_header(&data);
+    drop(first_run(&stream[..cut], &out));
+    let left = names(&out);
+    assert_eq!(left.len(), 2, "{:?}", left);
+    assert!(left[0].ends_with(".part") && left[1].ends_with(".part.journal"), "{:?}", left);
+
+    let Event::Received(file) = second_run(&stream[cut..], &out) else { panic!("файл не принят") };
+    assert_eq!(fs::read(&file.path).unwrap(), data);
+    assert_eq!(names(&out).len(), 1, "{:?}", names(&out));
+}
+
+#[test]
+fn crashed_receiver_keeps_the_first_copy() {
+    let dir = TempDir::new("copies");
+    let data = content(2000);
+    let options = SendOptions { repeat: 2, ..SendOptions::default() };
+    let mut stream = stream(&dir, &data, &options);
+    let copy_len = stream.len() / 2;
+    let layout = Layout::new(&options, data.len() as u64);
+    // На три ошибки больше предела: в первой копии испорчен блок 3, во второй — блок 5.
+    for (copy, block) in [(0, 3), (1, 5)] {
+        for offset in layout.ruin_block(block) {
+            stream[copy * copy_len + offset as usize] ^= 0xFF;
+        }
+    }
+    let out = dir.0.join("out");
+
+    // Сбой после первой копии: деструкторы не выполняются, остаётся то, что журнал успел записать.
+    std::mem::forget(first_run(&stream[..copy_len], &out));
+    let Event::Received(file) = second_run(&stream[copy_len..], &out) else { panic!("файл не принят") };
+    assert_eq!(fs::read(&file.path).unwrap(), data);
+    assert!(file.damage.is_none());
+    assert_eq!(names(&out).len(), 1, "{:?}", names(&out));
+}
+
+#[test]
+fn corrupt_journal_is_ignored() {
+    let dir = TempDir::new("corrupt");
+    let data = content(200_000);
+    let stream = stream(&dir, &data, &SendOptions::default());
+    let out = dir.0.join("out");
+    let cut = before_repeated_header(&data);
+    drop(first_run(&stream[..cut], &out));
+    let journal = out.join(names(&out).into_iter().find(|name| name.ends_with(".journal")).unwrap());
+    let mut bytes = fs::read(&journal).unwrap();
+    let last = bytes.len() - 10;
+    bytes[last] ^= 1;
+    fs::write(&journal, bytes).unwrap();
+
+    // Без журнала второй запуск начинает передачу заново с повтора заголовка, и начала файла у него нет.
+    assert!(matches!(second_run(&stream[cut..], &out), Event::Failed(_)));
+}
+
+/// Читает varint журнала с позиции `pos` и сдвигает её.
+fn varint(bytes: &[u8], pos: &mut usize) -> usize {
+    let mut value = 0;
+    let mut shift = 0;
+    loop {
+        let byte = bytes[*pos];
+        *pos += 1;
+        value |= ((byte & 0x7F) as usize) << shift;
+        if byte < 0x80 {
+            return value;
+        }
+        shift += 7;
+    }
+}
+
+/// Подменяет в журнале список исправленных блоков на `entry` и пересчитывает CRC,
+/// как если бы журнал записал кто-то другой.
+fn forge_corrected(journal: &Path, entry: (usize, usize, usize)) {
+    let bytes = fs::read(journal).unwrap();
+    let mut pos = 5;
+    pos += varint(&bytes, &mut pos);
+    pos += match bytes[pos] {
+        0 => 1,
+        _ => 33,
+    };
+    for _ in 0..2 {
+        pos += varint(&bytes, &mut pos);
+    }
+    let start = pos;
+    for _ in 0..varint(&bytes, &mut pos) * 3 {
+        varint(&bytes, &mut pos);
+    }
+    let mut forged = bytes[..start].to_vec();
+    for mut value in [1, entry.0, entry.1, entry.2] {
+        while value >= 0x80 {
+            forged.push(value as u8 | 0x80);
+            value >>= 7;
+        }
+        forged.push(value as u8);
+    }
+    forged.extend_from_slice(&bytes[pos..bytes.len() - 4]);
+    let crc = crc32fast::hash(&forged);
+    forged.extend_from_slice(&crc.to_be_bytes());
+    fs::write(journal, forged).unwrap();
+}
+
+#[test]
+fn journal_with_impossible_corrections_is_ignored() {
+    let dir = TempDir::new("forged");
+    let data = content(200_000);
+    let stream = stream(&dir, &data, &SendOptions::default());
+    let cut = before_repeated_header(&data);
+    // Блок 0 принят, последний — нет; `--rs 10` исправляет пять ошибок или десять стираний.
+    let last = Layout::new(&SendOptions::default(), data.len() as u64).header.block_count() - 1;
+    let cases = [
+        ("beyond", (0, 6, 0)),
+        ("mixed", (0, 3, 5)),
+        ("missing", (last, 1, 0)),
+        ("outside", (last + 1, 1, 0)),
+        ("empty", (0, 0, 0)),
+    ];
+    for (name, entry) in cases {
+        let out = dir.0.join(name);
+        drop(first_run(&stream[..cut], &out));
+        let journal = out.join(names(&out).into_iter().find(|name| name.ends_with(".journal")).unwrap());
+        forge_corrected(&journal, entry);
+        assert!(matches!(second_run(&stream[cut..], &out), Event::Failed(_)), "{}", name);
+    }
+
+    // Тот же журнал с возможным исправлением принимается.
+    let out = dir.0.join("valid");
+    drop(first_run(&stream[..cut], &out));
+    let journal = out.join(names(&out).into_iter().find(|name| name.ends_with(".journal")).unwrap());
+    forge_corrected(&journal, (0, 2, 6));
+    let Event::Received(file) = second_run(&stream[cut..], &out) else { panic!("журнал не принят") };
+    assert_eq!(fs::read(&file.path).unwrap(), data);
+    assert_eq!((file.stats.corrected_symbols, file.stats.erased_symbols), (2, 6));
+}
+
+#[test]
+fn receiver_without_journal_leaves_nothing() {
+    let dir = TempDir::new("nojournal");
+    let data = content(200_000);
+    let stream = stream(&dir, &data, &SendOptions::default());
+    let out = dir.0.join("out");
+    let (mut tx, rx) = pipe(TIMEOUT);
+    tx.write_all(&stream[..stream.len() / 2]).unwrap();
+    let mut receiver = Receiver::new(Box::new(rx), &out.to_string_lossy());
+    receiver.set_journal(false);
+    while !matches!(receiver.poll().unwrap(), Some(Event::Suspended(_))) {}
+    assert_eq!(names(&out).len(), 1, "{:?}", names(&out));
+
+    // Закрытый посреди передачи приёмник без журнала удаляет временный файл.
+    drop(receiver);
+    assert!(names(&out).is_empty(), "{:?}", names(&out));
+}
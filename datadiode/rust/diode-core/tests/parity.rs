@@ -0,0 +1,178 @@
+//! Режим с чётностью: байты, помеченные линией (PARMRK), исправляются как стирания.
+
+mod common;
+
+use std::fs;
+use std::io::{self, Read, Write};
+use std::thread;
+
+use diode_core::port::{ParityMarks, SerialConfig, PORT_TIMEOUT};
+use diode_core::transport::{pipe, PipeTx};
+use diode_core::{send, DecodeStats, Endpoint, Error, Event, Receiver, Rx, SendOptions, Tx};
+use serialport::Parity;
+use common::{Layout, TempDir};
+
+/// Пишет поток так, как его отдаёт драйвер в режиме PARMRK: `FF` удваивается, а байты
+/// с заданными смещениями инвертируются и, если `marked`, приходят с пометкой `FF 00`.
+struct ParmrkTx {
+    inner: PipeTx,
+    pos: u64,
+    flips: Vec<u64>,
+    marked: bool,
+}
+
+impl Write for ParmrkTx {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut out = Vec::with_capacity(buf.len() * 2);
+        for (i, &byte) in buf.iter().enumerate() {
+            if self.flips.contains(&(self.pos + i as u64)) {
+                if self.marked {
+                    out.extend_from_slice(&[0xFF, 0x00]);
+                }
+                out.push(!byte);
+            } else if byte == 0xFF {
+                out.extend_from_slice(&[0xFF, 0xFF]);
+            } else {
+                out.push(byte);
+            }
+        }
+        self.pos += buf.len() as u64;
+        self.inner.write_all(&out)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl Tx for ParmrkTx {}
+
+const FILE_LEN: u64 = 2000;
+
+/// `--rs 4`: две ошибки или четыре стирания на блок.
+fn options() -> SendOptions {
+    SendOptions { rs_bytes: 4, ..SendOptions::default() }
+}
+
+fn layout() -> Layout {
+    Layout::new(&options(), FILE_LEN)
+}
+
+fn receive(name: &str, flips: Vec<u64>, marked: bool) -> Result<DecodeStats, String> {
+    let dir = TempDir::new(name);
+    let path = dir.0.join("data.bin");
+    // Много байтов `FF`, чтобы проверить и их экранирование.
+    fs::write(&path, (0..FILE_LEN as u32).map(|i| if i % 5 == 0 { 0xFF } else { (i * 7) as u8 }).collect::<Vec<_>>()).unwrap();
+    let (pipe_tx, pipe_rx) = pipe(PORT_TIMEOUT);
+    let mut tx = ParmrkTx { inner: pipe_tx, pos: 0, flips, marked };
+    let sender = thread::spawn(move || send(&mut tx, &options(), path.to_str().unwrap()));
+    let mut receiver = Receiver::new(Box::new(ParityMarks::new(pipe_rx)), &dir.0.join("out").to_string_lossy());
+    let event = loop {
+        match receiver.poll() {
+            Ok(Some(event)) => break event,
+            Ok(None) => {}
+            Err(Error::Closed) => return Err("поток закончился без события".to_string()),
+            Err(e) => panic!("{}", e),
+        }
+    };
+    sender.join().unwrap().unwrap();
+    match event {
+        Event::Received(file) => Ok(file.stats),
+        Event::Failed(e) => Err(e.to_string()),
+        Event::Suspended(message) => Err(message),
+    }
+}
+
+/// Четыре ошибки в блоке с `--rs 4`: без отметок это вдвое больше предела.
+fn payload_flips() -> Vec<u64> {
+    [3, 80, 150, 240].into_iter().map(|byte| layout().block_offset(0, byte)).collect()
+}
+
+#[test]
+fn marked_bytes_are_corrected_as_erasures() {
+    let stats = receive("erasures", payload_flips(), true).unwrap();
+    assert_eq!(stats.line_marks, 4);
+    // Стирания не ошибки: предел в две ошибки на блок они не превышают.
+    assert_eq!((stats.corrected_symbols, stats.erased_symbols, stats.corrected_blocks), (0, 4, 1));
+    assert_eq!(stats.worst_block, None);
+    assert_eq!(stats.uncorrectable, 0);
+    let text = stats.to_string();
+    assert!(text.contains("восстановлено стираний 4") && text.contains("отмечено линией 4"), "{}", text);
+}
+
+#[test]
+fn unmarked_errors_beyond_the_limit_are_lost() {
+    assert!(receive("unmarked", payload_flips(), false).is_err());
+}
+
+#[test]
+fn marked_frame_fields_are_corrected_as_erasures() {
+    // Три ошибки в служебных полях кадра: их короткий код исправляет две ошибки или четыре стирания.
+    let flips = [5, 9, 17].into_iter().map(|byte| layout().frame_start(1) + byte).collect::<Vec<_>>();
+    let stats = receive("fields", flips.clone(), true).unwrap();
+    assert_eq!((stats.line_marks, stats.corrected_symbols), (0, 0));
+    assert!(receive("fields-unmarked", flips, false).is_err());
+}
+
+/// Отдаёт поток по одному байту, чтобы пометки рвались между чтениями.
+struct ByteByByte(io::Cursor<Vec<u8>>);
+
+impl Read for ByteByByte {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = buf.len().min(1);
+        self.0.read(&mut buf[..len])
+    }
+}
+
+impl Rx for ByteByByte {}
+
+#[test]
+fn marks_split_between_reads_are_decoded() {
+    let raw = vec![b'A', 0xFF, 0xFF, 0xFF, 0x00, b'B', b'C', 0xFF, 0x00, 0xFF, b'D'];
+    let mut rx = ParityMarks::new(ByteByByte(io::Cursor::new(raw)));
+    let (mut data, mut marks) = (Vec::new(), Vec::new());
+    let (mut buf, mut mark) = ([0u8; 4], [false; 4]);
+    loop {
+        let n = rx.read_marked(&mut buf, &mut mark).unwrap();
+        if n == 0 {
+            break;
+        }
+        data.extend_from_slice(&buf[..n]);
+        marks.extend_from_slice(&mark[..n]);
+    }
+    assert_eq!(data, [b'A', 0xFF, b'B', b'C', 0xFF, b'D']);
+    assert_eq!(marks, [false, false, true, false, true, false]);
+}
+
+#[test]
+fn parity_is_a_port_parameter() {
+    let config = SerialConfig::parse("/dev/ttyUSB0?parity=even").unwrap();
+    assert_eq!(config, SerialConfig { name: "/dev/ttyUSB0".to_string(), parity: Parity::Even });
+    assert_eq!(Endpoint::parse("COM3").unwrap(), Endpoint::Serial(SerialConfig { name: "COM3".to_string(), parity: Parity::None }));
+    assert_eq!(Endpoint::parse("COM3?parity=odd").unwrap().to_string(), "COM3?parity=odd");
+    assert!(matches!(SerialConfig::parse("COM3?parity=mark"), Err(Error::Policy(_))));
+    assert!(matches!(SerialConfig::parse("COM3?stop=2"), Err(Error::Policy(_))));
+}
+
+/// На псевдотерминале ошибок чётности не бывает, но PARMRK включается по-настоящему:
+/// драйвер удваивает `FF`, и приёмник должен получить его один раз и без отметки.
+#[cfg(target_os = "linux")]
+#[test]
+fn pty_with_parity_delivers_ff_unmarked() {
+    use serialport::{SerialPort, TTYPort};
+
+    let (mut master, slave) = TTYPort::pair().unwrap();
+    let name = slave.name().unwrap();
+    let mut rx = Endpoint::parse(&format!("{}?parity=even", name)).unwrap().open_rx(115200).unwrap();
+    master.write_all(&[0x01, 0xFF, 0x02]).unwrap();
+    let (mut data, mut marks) = (Vec::new(), Vec::new());
+    let (mut buf, mut mark) = ([0u8; 16], [true; 16]);
+    while data.len() < 3 {
+        let n = rx.read_marked(&mut buf, &mut mark).unwrap();
+        data.extend_from_slice(&buf[..n]);
+        marks.extend_from_slice(&mark[..n]);
+    }
+    assert_eq!(data, [0x01, 0xFF, 0x02]);
+    assert_eq!(marks, [false; 3]);
+}
@@ -0,0 +1,82 @@
+//! Планировщик: объём в линии совпадает с настоящей отправкой, вероятности ведут себя разумно.
+
+mod common;
+
+use std::fs;
+
+use diode_core::plan::{estimate, plan, Link};
+use diode_core::{send, Error, FecMode, SendOptions};
+use common::TempDir;
+
+fn link(ber: f64) -> Link {
+    Link { baud: 921600, bits_per_byte: 10, ber }
+}
+
+#[test]
+fn wire_bytes_match_the_sender() {
+    let dir = TempDir::new("wire");
+    let path = dir.0.join("data.bin");
+    for (size, options) in [
+        (100_000, SendOptions { rs_bytes: 16, interleave: 4, repeat: 2, ..SendOptions::default() }),
+        (1, SendOptions::default()),
+        // Больше 256 кадров в копии: заголовок повторяется внутри неё.
+        (150_000, SendOptions::default()),
+        (0, SendOptions::default()),
+        (300_000, SendOptions { fec: FecMode::RaptorQ { repair_percent: 20 }, ..SendOptions::default() }),
+    ] {
+        fs::write(&path, vec![0x5A; size]).unwrap();
+        let out = dir.0.join("stream.bin");
+        send(&mut fs::File::create(&out).unwrap(), &options, path.to_str().unwrap()).unwrap();
+        let sent = fs::metadata(&out).unwrap().len();
+        assert_eq!(estimate(size as u64, &options, &link(0.0)).unwrap().wire_bytes, sent, "{:?}", options);
+    }
+}
+
+#[test]
+fn time_follows_baud_and_framing() {
+    let options = SendOptions::default();
+    let fast = estimate(1_000_000, &options, &link(0.0)).unwrap();
+    let slow = estimate(1_000_000, &options, &Link { baud: 460800, bits_per_byte: 11, ber: 0.0 }).unwrap();
+    let expected = fast.wire_bytes as f64 * 10.0 / 921600.0;
+    assert!((fast.duration.as_secs_f64() - expected).abs() < 1e-6);
+    assert!((slow.duration.as_secs_f64() / fast.duration.as_secs_f64() - 2.2).abs() < 1e-6);
+    assert!(fast.throughput < 92160.0 && fast.throughput > 80000.0);
+}
+
+#[test]
+fn failure_grows_with_ber_and_falls_with_copies() {
+    let options = SendOptions { rs_bytes: 8, ..SendOptions::default() };
+    let clean = estimate(10_000_000, &options, &link(0.0)).unwrap();
+    assert_eq!(clean.file_failure, 0.0);
+    let low = estimate(10_000_000, &options, &link(1e-5)).unwrap();
+    let high = estimate(10_000_000, &options, &link(1e-4)).unwrap();
+    assert!(0.0 < low.file_failure && low.file_failure < high.file_failure);
+    let twice = estimate(10_000_000, &SendOptions { repeat: 2, ..options }, &link(1e-4)).unwrap();
+    assert!(twice.file_failure < high.file_failure);
+    assert!((twice.unit_failure - high.unit_failure.powi(2)).abs() <= high.unit_failure.powi(2) * 1e-9);
+}
+
+#[test]
+fn recommends_the_cheapest_reliable_setting() {
+    let clean = plan(10_000_000, &link(0.0), 1, 1e-6).unwrap();
+    let best = &clean.estimates[clean.recommended.unwrap()].options;
+    assert_eq!((best.fec, best.rs_bytes, best.repeat), (FecMode::ReedSolomon, 2, 1));
+
+    let noisy = plan(10_000_000, &link(1e-4), 1, 1e-6).unwrap();
+    let best = &noisy.estimates[noisy.recommended.unwrap()];
+    assert!(best.file_failure <= 1e-6);
+    for estimate in &noisy.estimates {
+        assert!(estimate.file_failure > 1e-6 || estimate.wire_bytes >= best.wire_bytes, "{}", estimate);
+    }
+
+    let hopeless = plan(10_000_000, &link(0.05), 1, 1e-6).unwrap();
+    assert_eq!(hopeless.recommended, None);
+}
+
+#[test]
+fn invalid_inputs_are_rejected() {
+    assert!(matches!(plan(1000, &link(0.7), 1, 1e-6), Err(Error::Policy(_))));
+    assert!(matches!(plan(1000, &link(1e-6), 1, 0.0), Err(Error::Policy(_))));
+    assert!(matches!(plan(1000, &link(1e-6), 0, 1e-6), Err(Error::Policy(_))));
+    assert!(matches!(plan(1000, &Link { baud: 0, ..link(1e-6) }, 1, 1e-6), Err(Error::Policy(_))));
+}
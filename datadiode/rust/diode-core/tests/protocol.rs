@@ -0,0 +1,32 @@
+//! Заголовок передачи: приёмник отвергает параметры, которых отправитель не создаёт.
+
+use diode_core::fec::{RAPTORQ_SEGMENT_LEN, RAPTORQ_SEGMENT_SYMBOLS, RAPTORQ_SYMBOL_SIZE};
+use diode_core::protocol::{Fec, Header, PROTOCOL_VERSION};
+use diode_core::Error;
+
+fn raptorq_header(symbol_size: u16, segment_symbols: u16) -> Header {
+    Header {
+        version: PROTOCOL_VERSION,
+        rs_bytes: 10,
+        block_len: 245,
+        interleave: 1,
+        fec: Fec::RaptorQ { symbol_size, segment_symbols, repair_percent: 20 },
+        flags: 0,
+        file_size: 1 << 40,
+        transfer_id: 1,
+        copies: 1,
+        fragment_len: 0,
+        filename: "data.bin".to_string(),
+    }
+}
+
+#[test]
+fn oversized_raptorq_segment_is_rejected() {
+    let header = raptorq_header(RAPTORQ_SYMBOL_SIZE, RAPTORQ_SEGMENT_SYMBOLS);
+    assert_eq!(Header::decode(&header.encode()).unwrap(), header);
+    assert_eq!(header.segment_len(), Some(RAPTORQ_SEGMENT_LEN));
+
+    // Каждое поле в своих пределах, но сегмент вышел бы в несколько гигабайт.
+    let result = Header::decode(&raptorq_header(60_000, 50_000).encode());
+    assert!(matches!(result, Err(Error::Protocol(_))), "{:?}", result);
+}
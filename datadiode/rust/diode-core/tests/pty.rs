@@ -0,0 +1,274 @@
+//! Сквозные тесты: настоящие отправитель и приёмник обмениваются файлами через пары
+//! псевдотерминалов Linux, как через два адаптера CP2102, соединённых линией.
+#![cfg(target_os = "linux")]
+
+mod common;
+
+use std::fs;
+use std::io::{ErrorKind, Read, Write};
+use std::path::PathBuf;
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+use diode_core::port::PORT_TIMEOUT;
+use diode_core::protocol::{encode_frame, Fec, Header, FRAME_HEADER, PROTOCOL_VERSION};
+use diode_core::{send, Error, Event, FecMode, Receiver, SendOptions};
+use serialport::{SerialPort, TTYPort};
+use common::TempDir;
+
+/// Сколько ждать события приёмника, прежде чем считать тест зависшим.
+const RECEIVE_DEADLINE: Duration = Duration::from_secs(120);
+
+/// Связанная пара псевдотерминалов: что записано в один конец, читается из другого.
+fn pty_pair() -> (Box<dyn SerialPort>, Box<dyn SerialPort>) {
+    let (mut a, mut b) = TTYPort::pair().expect("не удалось создать пару псевдотерминалов");
+    a.set_timeout(PORT_TIMEOUT).unwrap();
+    b.set_timeout(PORT_TIMEOUT).unwrap();
+    (Box::new(a), Box::new(b))
+}
+
+/// Детерминированные псевдослучайные байты (xorshift).
+fn pattern(len: usize, seed: u64) -> Vec<u8> {
+    let mut state = seed | 1;
+    (0..len)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state as u8
+        })
+        .collect()
+}
+
+/// Переносит байты с одного псевдотерминала на другой, инвертируя каждый бит с вероятностью
+/// `ber`, пока отправитель не закроет свой конец.
+fn noisy_link(mut from: Box<dyn SerialPort>, mut to: Box<dyn SerialPort>, ber: f64, seed: u64) -> thread::JoinHandle<()> {
+    thread::spawn(move || {
+        let mut state = seed | 1;
+        let mut buf = [0u8; 4096];
+        let threshold = (ber * u64::MAX as f64) as u64;
+        loop {
+            let n = match from.read(&mut buf) {
+                Ok(0) => return,
+                Ok(n) => n,
+                Err(e) if e.kind() == ErrorKind::TimedOut => continue,
+                Err(_) => return,
+            };
+            for byte in &mut buf[..n] {
+                for bit in 0..8 {
+                    state ^= state << 13;
+                    state ^= state >> 7;
+                    state ^= state << 17;
+                    if state < threshold {
+                        *byte ^= 1 << bit;
+                    }
+                }
+            }
+            if to.write_all(&buf[..n]).is_err() {
+                return;
+            }
+        }
+    })
+}
+
+/// Запускает приёмник, который читает порт, пока его не закроют, и присылает события.
+/// Закрывать отправителя можно только после события: при закрытии псевдотерминала
+/// непрочитанные байты пропадают. А читать дальше нужно, потому что приёмник может завершить
+/// передачу раньше, чем отправитель допишет лишние копии.
+fn start_receiver(rx: Box<dyn SerialPort>, output_dir: PathBuf) -> (mpsc::Receiver<Event>, thread::JoinHandle<()>) {
+    let (events, received) = mpsc::channel();
+    let handle = thread::spawn(move || {
+        let mut receiver = Receiver::new(Box::new(rx), output_dir.to_str().unwrap());
+        loop {
+            match receiver.poll() {
+                Ok(Some(event)) => {
+                    let _ = events.send(event);
+                }
+                Ok(None) => {}
+                // Закрытый псевдотерминал читается с ошибкой.
+                Err(_) => return,
+            }
+        }
+    });
+    (received, handle)
+}
+
+fn wait_event(events: &mpsc::Receiver<Event>) -> Event {
+    events.recv_timeout(RECEIVE_DEADLINE).expect("приёмник не дождался передачи")
+}
+
+/// Записывает `data` в файл `name` и отправляет его через пару псевдотерминалов.
+fn round_trip(dir: &TempDir, name: &str, data: &[u8], options: &SendOptions) -> Event {
+    let source = dir.0.join("source");
+    fs::create_dir_all(&source).unwrap();
+    let path = source.join(name);
+    fs::write(&path, data).unwrap();
+
+    let (mut tx, rx) = pty_pair();
+    let (events, receiver) = start_receiver(rx, dir.0.join("out"));
+    send(&mut tx, options, path.to_str().unwrap()).expect("отправка не удалась");
+    let event = wait_event(&events);
+    drop(tx);
+    receiver.join().unwrap();
+    event
+}
+
+/// Проверяет, что файл принят байт в байт под именем из заголовка и временных файлов не осталось.
+fn assert_received(event: Event, dir: &TempDir, name: &str, data: &[u8]) {
+    let file = match event {
+        Event::Received(file) => file,
+        event => panic!("передача не удалась: {:?}", event),
+    };
+    assert!(file.path.ends_with(&format!("_{}", name)), "неожиданное имя: {}", file.path);
+    assert_eq!(fs::read(&file.path).unwrap(), data);
+    let leftovers: Vec<_> = fs::read_dir(dir.0.join("out"))
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name())
+        .filter(|name| name.to_string_lossy().ends_with(".part"))
+        .collect();
+    assert!(leftovers.is_empty(), "остались временные файлы: {:?}", leftovers);
+}
+
+#[test]
+fn empty_file() {
+    let dir = TempDir::new("empty");
+    let event = round_trip(&dir, "empty.bin", &[], &SendOptions::default());
+    assert_received(event, &dir, "empty.bin", &[]);
+}
+
+#[test]
+fn tiny_files() {
+    // Размеры вокруг границы блока: при 10 проверочных байтах в блоке 245 байт данных.
+    for len in [1, 2, 244, 245, 246] {
+        let dir = TempDir::new(&format!("tiny-{}", len));
+        let data = pattern(len, len as u64);
+        let event = round_trip(&dir, "tiny.bin", &data, &SendOptions::default());
+        assert_received(event, &dir, "tiny.bin", &data);
+    }
+}
+
+#[test]
+fn multi_megabyte_file() {
+    let dir = TempDir::new("large");
+    let data = pattern(3 * 1024 * 1024 + 17, 7);
+    let options = SendOptions { rs_bytes: 32, interleave: 8, ..SendOptions::default() };
+    let event = round_trip(&dir, "large.bin", &data, &options);
+    assert_received(event, &dir, "large.bin", &data);
+}
+
+#[test]
+fn raptorq_file() {
+    let dir = TempDir::new("raptorq");
+    let data = pattern(100_000, 11);
+    let options = SendOptions { fec: FecMode::RaptorQ { repair_percent: 10 }, ..SendOptions::default() };
+    let event = round_trip(&dir, "raptorq.bin", &data, &options);
+    assert_received(event, &dir, "raptorq.bin", &data);
+}
+
+#[test]
+fn unicode_name() {
+    let dir = TempDir::new("unicode");
+    let name = "Отчёт за квартал ✓ 📁.txt";
+    let data = "Съешь же ещё этих мягких французских булок".as_bytes();
+    let event = round_trip(&dir, name, data, &SendOptions::default());
+    assert_received(event, &dir, name, data);
+}
+
+#[test]
+fn repeated_transfer() {
+    let dir = TempDir::new("repeat");
+    let data = pattern(50_000, 3);
+    let options = SendOptions { repeat: 3, ..SendOptions::default() };
+    let event = round_trip(&dir, "repeat.bin", &data, &options);
+    assert_received(event, &dir, "repeat.bin", &data);
+}
+
+#[test]
+fn invalid_rs_settings_are_rejected() {
+    let dir = TempDir::new("invalid");
+    let path = dir.0.join("file.bin");
+    fs::write(&path, pattern(1000, 5)).unwrap();
+    let (mut tx, _rx) = pty_pair();
+    for options in [
+        SendOptions { rs_bytes: 255, ..SendOptions::default() },
+        SendOptions { interleave: 0, ..SendOptions::default() },
+        SendOptions { repeat: 0, ..SendOptions::default() },
+    ] {
+        let result = send(&mut tx, &options, path.to_str().unwrap());
+        assert!(matches!(result, Err(Error::Policy(_))), "{:?}: {:?}", options, result);
+    }
+}
+
+/// Заголовок, повреждённый сверх возможностей RS, приёмник пропускает, а заголовок с параметрами
+/// вне допустимых сообщает как ошибку протокола; следующая передача принимается как обычно.
+#[test]
+fn bad_headers_do_not_break_the_receiver() {
+    let dir = TempDir::new("bad-header");
+    let path = dir.0.join("file.bin");
+    let data = pattern(5000, 13);
+    fs::write(&path, &data).unwrap();
+    let (mut tx, rx) = pty_pair();
+    let (events, receiver) = start_receiver(rx, dir.0.join("out"));
+
+    let header = Header {
+        version: PROTOCOL_VERSION,
+        rs_bytes: 10,
+        block_len: 245,
+        interleave: 1,
+        fec: Fec::ReedSolomon,
+        flags: 0,
+        file_size: 5000,
+        transfer_id: 1,
+        copies: 1,
+        fragment_len: 0,
+        filename: "bad.bin".to_string(),
+    };
+    let mut garbled = header.encode();
+    garbled.iter_mut().step_by(3).for_each(|byte| *byte ^= 0x5A);
+    // Проверочных байтов и длины блока в сумме больше кодового слова RS.
+    let out_of_range = Header { block_len: 250, ..header }.encode();
+    for payload in [garbled, out_of_range] {
+        tx.write_all(&encode_frame(FRAME_HEADER, 0, &payload)).unwrap();
+    }
+    send(&mut tx, &SendOptions::default(), path.to_str().unwrap()).unwrap();
+
+    match wait_event(&events) {
+        Event::Failed(e) => assert!(matches!(*e, Error::Protocol(_)), "{}", e),
+        event => panic!("заголовок не отвергнут: {:?}", event),
+    }
+    assert_received(wait_event(&events), &dir, "file.bin", &data);
+    drop(tx);
+    receiver.join().unwrap();
+}
+
+/// Слабый RS на зашумлённой линии не должен выдать испорченный файл за принятый,
+/// а достаточный RS на той же линии файл восстанавливает.
+#[test]
+fn noisy_link_needs_enough_rs() {
+    let data = pattern(50_000, 9);
+    for (rs_bytes, expect_ok) in [(2, false), (32, true)] {
+        let dir = TempDir::new(&format!("noisy-{}", rs_bytes));
+        let path = dir.0.join("noisy.bin");
+        fs::write(&path, &data).unwrap();
+
+        let (mut tx, link_in) = pty_pair();
+        let (link_out, rx) = pty_pair();
+        let link = noisy_link(link_in, link_out, 5e-4, 13);
+        let (events, receiver) = start_receiver(rx, dir.0.join("out"));
+        let options = SendOptions { rs_bytes, ..SendOptions::default() };
+        send(&mut tx, &options, path.to_str().unwrap()).expect("отправка не удалась");
+        let event = wait_event(&events);
+        drop(tx);
+        link.join().unwrap();
+        receiver.join().unwrap();
+
+        if expect_ok {
+            assert_received(event, &dir, "noisy.bin", &data);
+        } else {
+            assert!(matches!(event, Event::Failed(_)), "слабый RS не должен справиться с шумом");
+            let out = dir.0.join("out");
+            assert_eq!(fs::read_dir(&out).unwrap().count(), 0, "после неудачи в {} остались файлы", out.display());
+        }
+    }
+}
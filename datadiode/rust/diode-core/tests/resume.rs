@@ -0,0 +1,231 @@
+//! Продолжение прерванной отправки: отправитель сохраняет контрольную точку, приёмник
+//! откладывает оборвавшуюся передачу и дописывает продолжение в тот же временный файл.
+
+mod common;
+
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+use diode_core::checkpoint::RESUME_OVERLAP;
+use diode_core::transport::{pipe, PipeTx};
+use diode_core::{resume, send, Checkpoint, Error, Event, Receiver, SendOptions, Tx};
+use common::TempDir;
+
+/// Адаптер, который пропадает, пропустив `left` байт.
+struct VanishingTx<'a> {
+    inner: &'a mut PipeTx,
+    left: usize,
+}
+
+impl Write for VanishingTx<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.left == 0 {
+            return Err(io::Error::new(io::ErrorKind::BrokenPipe, "адаптер пропал"));
+        }
+        let n = buf.len().min(self.left);
+        self.left -= n;
+        self.inner.write(&buf[..n])
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl Tx for VanishingTx<'_> {}
+
+/// Таймаут приёмника: пауза дольше него означает, что поток оборвался.
+const TIMEOUT: Duration = Duration::from_millis(300);
+const FILE_LEN: usize = 300_000;
+/// Блок данных RS при `--rs 10`: при перемежении 1 кусок отправки — один блок.
+const BLOCK_LEN: usize = 245;
+
+fn content() -> Vec<u8> {
+    (0..FILE_LEN as u32).map(|i| (i * 31 + i / 1000) as u8).collect()
+}
+
+/// Принимает события, пока источник не закроется.
+fn receive_all(mut receiver: Receiver) -> Vec<Event> {
+    let mut events = Vec::new();
+    loop {
+        match receiver.poll() {
+            Ok(Some(event)) => events.push(event),
+            Ok(None) => {}
+            Err(Error::Closed) => return events,
+            Err(e) => panic!("{}", e),
+        }
+    }
+}
+
+/// Отправляет файл с контрольной точкой в `checkpoint` до обрыва через `cut` байт и ждёт, пока
+/// приёмник заметит паузу; затем `then` продолжает. Возвращает события приёмника.
+fn interrupted(
+    dir: &TempDir,
+    checkpoint: &Path,
+    cut: usize,
+    then: impl FnOnce(&mut PipeTx) + Send + 'static,
+) -> (Vec<Event>, PathBuf) {
+    let source = dir.0.join("data.bin");
+    fs::write(&source, content()).unwrap();
+    let out = dir.0.join("out");
+    let (mut pipe_tx, pipe_rx) = pipe(TIMEOUT);
+    let options = SendOptions { checkpoint: Some(checkpoint.to_path_buf()), ..SendOptions::default() };
+    let (events_tx, events_rx) = mpsc::channel();
+    let receiver = Receiver::new(Box::new(pipe_rx), &out.to_string_lossy());
+    let receiving = thread::spawn(move || events_tx.send(receive_all(receiver)).unwrap());
+
+    let result = send(&mut VanishingTx { inner: &mut pipe_tx, left: cut }, &options, source.to_str().unwrap());
+    assert!(matches!(result, Err(Error::Io { .. })), "{:?}", result);
+    thread::sleep(TIMEOUT * 3);
+    then(&mut pipe_tx);
+    drop(pipe_tx);
+    receiving.join().unwrap();
+    (events_rx.recv().unwrap(), out)
+}
+
+fn assert_interrupted(event: &Event) {
+    match event {
+        Event::Suspended(message) => assert!(message.contains("прервана"), "{}", message),
+        event => panic!("передача завершилась до продолжения: {:?}", event),
+    }
+}
+
+#[test]
+fn resume_completes_the_interrupted_file() {
+    let dir = TempDir::new("complete");
+    let checkpoint = dir.0.join("send.checkpoint");
+    let path = checkpoint.clone();
+    let (events, out) = interrupted(&dir, &checkpoint, 200_000, move |tx| {
+        // Отправка шла меньше секунды и точку после начала не сохраняла: сдвигаем её туда,
+        // куда она встала бы позже, чтобы продолжение не повторяло начало файла.
+        let mut saved = Checkpoint::load(&path).unwrap();
+        assert_eq!((saved.copy, saved.next), (0, 0));
+        saved.next = 600;
+        saved.save(&path).unwrap();
+        let mut counting = VanishingTx { inner: tx, left: usize::MAX };
+        resume(&mut counting, &path).unwrap();
+        let sent = usize::MAX - counting.left;
+        assert!(sent < FILE_LEN, "продолжение отправило {} байт", sent);
+    });
+
+    assert_eq!(events.len(), 2, "{:?}", events);
+    assert_interrupted(&events[0]);
+    let Event::Received(file) = &events[1] else { panic!("{:?}", events[1]) };
+    assert_eq!(fs::read(&file.path).unwrap(), content());
+    assert!(!checkpoint.exists());
+    assert_eq!(fs::read_dir(&out).unwrap().count(), 1);
+}
+
+#[test]
+fn resume_starts_before_the_checkpoint() {
+    let dir = TempDir::new("overlap");
+    let checkpoint = dir.0.join("send.checkpoint");
+    let path = checkpoint.clone();
+    // Точка ушла дальше обрыва: последние кадры застряли в буферах и пропали вместе
+    // с адаптером. Их покрывает запас, с которым начинается продолжение.
+    let (events, _) = interrupted(&dir, &checkpoint, 200_000, move |tx| {
+        let mut saved = Checkpoint::load(&path).unwrap();
+        saved.next = (200_000 + RESUME_OVERLAP as usize / 2) / BLOCK_LEN;
+        saved.save(&path).unwrap();
+        resume(tx, &path).unwrap();
+    });
+    let Some(Event::Received(file)) = events.last() else { panic!("{:?}", events) };
+    assert_eq!(fs::read(&file.path).unwrap(), content());
+}
+
+#[test]
+fn changed_file_is_not_resumed() {
+    let dir = TempDir::new("changed");
+    let checkpoint = dir.0.join("send.checkpoint");
+    let path = checkpoint.clone();
+    let source = dir.0.join("data.bin");
+    let (events, out) = interrupted(&dir, &checkpoint, 50_000, move |tx| {
+        let mut data = content();
+        data[100] ^= 1;
+        fs::write(&source, data).unwrap();
+        assert!(matches!(resume(tx, &path), Err(Error::Integrity(_))));
+    });
+    // Конец потока завершает и отложенную передачу: временный файл удаляется.
+    assert_eq!(events.len(), 2, "{:?}", events);
+    assert_interrupted(&events[0]);
+    assert!(matches!(&events[1], Event::Failed(_)), "{:?}", events[1]);
+    assert_eq!(fs::read_dir(&out).unwrap().count(), 0);
+}
+
+#[test]
+fn finished_send_removes_the_checkpoint() {
+    let dir = TempDir::new("finished");
+    let source = dir.0.join("data.bin");
+    fs::write(&source, &content()[..10_000]).unwrap();
+    let checkpoint = dir.0.join("send.checkpoint");
+    let (mut tx, _rx) = pipe(TIMEOUT);
+    let options = SendOptions { checkpoint: Some(checkpoint.clone()), repeat: 2, ..SendOptions::default() };
+    send(&mut tx, &options, source.to_str().unwrap()).unwrap();
+    assert!(!checkpoint.exists());
+    assert!(matches!(resume(&mut tx, &checkpoint), Err(Error::Io { .. })));
+}
+
+#[test]
+fn unresumed_transfer_expires() {
+    let dir = TempDir::new("expires");
+    let source = dir.0.join("data.bin");
+    fs::write(&source, content()).unwrap();
+    let out = dir.0.join("out");
+    let (mut pipe_tx, pipe_rx) = pipe(TIMEOUT);
+    let mut receiver = Receiver::new(Box::new(pipe_rx), &out.to_string_lossy());
+    receiver.set_salvage(true);
+    receiver.set_resume_timeout(TIMEOUT * 2);
+    let (events_tx, events_rx) = mpsc::channel();
+    let receiving = thread::spawn(move || {
+        while let Ok(event) = receiver.poll() {
+            if let Some(event) = event {
+                events_tx.send(event).unwrap();
+            }
+        }
+    });
+
+    let result = send(&mut VanishingTx { inner: &mut pipe_tx, left: 100_000 }, &SendOptions::default(), source.to_str().unwrap());
+    assert!(result.is_err());
+    assert_interrupted(&events_rx.recv_timeout(TIMEOUT * 5).unwrap());
+    // Линия молчит, но не закрыта: отложенная передача завершается сама и сохраняется частично.
+    let event = events_rx.recv_timeout(TIMEOUT * 5).unwrap();
+    let Event::Received(file) = &event else { panic!("{:?}", event) };
+    assert!(file.damage.is_some(), "{}", file.path);
+    // Временный файл и журнал убраны: остался только частичный файл с картами.
+    let names: Vec<_> = fs::read_dir(&out).unwrap().map(|e| e.unwrap().file_name().into_string().unwrap()).collect();
+    assert!(names.iter().all(|name| name.starts_with("partial_")), "{:?}", names);
+    drop(pipe_tx);
+    receiving.join().unwrap();
+}
+
+#[test]
+fn checkpoint_past_the_transfer_is_rejected() {
+    let dir = TempDir::new("range");
+    let checkpoint = dir.0.join("send.checkpoint");
+    let source = dir.0.join("data.bin");
+    fs::write(&source, content()).unwrap();
+    let (mut pipe_tx, _rx) = pipe(TIMEOUT);
+    let options = SendOptions { checkpoint: Some(checkpoint.clone()), ..SendOptions::default() };
+    let result = send(&mut VanishingTx { inner: &mut pipe_tx, left: 50_000 }, &options, source.to_str().unwrap());
+    assert!(result.is_err());
+    let saved = Checkpoint::load(&checkpoint).unwrap();
+
+    let chunks = FILE_LEN.div_ceil(BLOCK_LEN);
+    for (copy, next) in [(1, 0), (0, chunks + 1)] {
+        Checkpoint { copy, next, ..saved.clone() }.save(&checkpoint).unwrap();
+        let mut counting = VanishingTx { inner: &mut pipe_tx, left: usize::MAX };
+        let result = resume(&mut counting, &checkpoint);
+        assert!(matches!(result, Err(Error::Policy(_))), "{:?}", result);
+        assert_eq!(counting.left, usize::MAX, "отправлено по неверной точке");
+        assert!(checkpoint.exists());
+    }
+
+    // Точка в самом конце копии допустима: остаётся отправить трейлер.
+    Checkpoint { next: chunks, ..saved }.save(&checkpoint).unwrap();
+    resume(&mut pipe_tx, &checkpoint).unwrap();
+    assert!(!checkpoint.exists());
+}
@@ -0,0 +1,229 @@
+//! Порт на сервере портов по RFC 2217 против сервера-заглушки на `TcpListener`.
+
+use std::io::{self, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use diode_core::port::{SerialConfig, PORT_TIMEOUT};
+use diode_core::rfc2217::Rfc2217Port;
+use diode_core::{Endpoint, Error};
+use serialport::Parity;
+
+const IAC: u8 = 255;
+const DONT: u8 = 254;
+const DO: u8 = 253;
+const WONT: u8 = 252;
+const WILL: u8 = 251;
+const SB: u8 = 250;
+const SE: u8 = 240;
+const NOP: u8 = 241;
+const OPT_ECHO: u8 = 1;
+const OPT_TTYPE: u8 = 24;
+const OPT_COM_PORT: u8 = 44;
+const SET_PARITY: u8 = 3;
+const PARITY_NONE: u8 = 1;
+const PARITY_EVEN: u8 = 3;
+
+/// Что прислал клиент: данные без удвоений, подопции и команды согласования.
+#[derive(Debug, Default)]
+struct Parsed {
+    data: Vec<u8>,
+    subs: Vec<Vec<u8>>,
+    commands: Vec<(u8, u8)>,
+}
+
+fn parse(raw: &[u8]) -> Parsed {
+    let mut parsed = Parsed::default();
+    let mut i = 0;
+    while i < raw.len() {
+        match raw[i..] {
+            [IAC, IAC, ..] => {
+                parsed.data.push(IAC);
+                i += 2;
+            }
+            [IAC, SB, ..] => {
+                let Some(end) = raw[i..].windows(2).position(|w| w == [IAC, SE]) else { break };
+                parsed.subs.push(parse(&raw[i + 2..i + end]).data);
+                i += end + 2;
+            }
+            [IAC, command, option, ..] if (WILL..=DONT).contains(&command) => {
+                parsed.commands.push((command, option));
+                i += 3;
+            }
+            [IAC, ..] => i += 2,
+            _ => {
+                parsed.data.push(raw[i]);
+                i += 1;
+            }
+        }
+    }
+    parsed
+}
+
+/// Дочитывает в `raw` байты клиента, пока разобранное не удовлетворит `done`.
+fn read_until(stream: &mut TcpStream, raw: &mut Vec<u8>, done: impl Fn(&Parsed) -> bool) -> Parsed {
+    let mut chunk = [0u8; 1024];
+    loop {
+        let parsed = parse(raw);
+        if done(&parsed) {
+            return parsed;
+        }
+        let n = stream.read(&mut chunk).unwrap();
+        assert!(n > 0, "клиент закрыл соединение: {:?}", parsed);
+        raw.extend_from_slice(&chunk[..n]);
+    }
+}
+
+/// Ответ сервера на подопцию: та же команда с кодом больше на 100 и установленное значение.
+fn ack(sub: &[u8], value: &[u8]) -> Vec<u8> {
+    let mut out = vec![IAC, SB, OPT_COM_PORT, sub[1] + 100];
+    for &byte in value {
+        out.push(byte);
+        if byte == IAC {
+            out.push(IAC);
+        }
+    }
+    out.extend_from_slice(&[IAC, SE]);
+    out
+}
+
+/// Сервер-заглушка на свободном порту: `serve` обслуживает первое соединение.
+fn server<T: Send + 'static>(serve: impl FnOnce(TcpStream) -> T + Send + 'static) -> (String, JoinHandle<T>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap().to_string();
+    let handle = thread::spawn(move || {
+        let (stream, _) = listener.accept().unwrap();
+        stream.set_read_timeout(Some(Duration::from_secs(10))).unwrap();
+        serve(stream)
+    });
+    (addr, handle)
+}
+
+/// Дожидается всех пяти параметров линии и подтверждает их; `answer` может подменить значение.
+fn negotiate(stream: &mut TcpStream, answer: impl Fn(&[u8]) -> Vec<u8>) -> Parsed {
+    let mut raw = Vec::new();
+    let parsed = read_until(stream, &mut raw, |parsed| parsed.subs.len() == 5);
+    for sub in &parsed.subs {
+        stream.write_all(&ack(sub, &answer(sub))).unwrap();
+    }
+    parsed
+}
+
+fn config(addr: &str, parity: Parity) -> SerialConfig {
+    SerialConfig { name: addr.to_string(), parity }
+}
+
+#[test]
+fn negotiates_the_line_and_refuses_unknown_options() {
+    let (addr, handle) = server(|mut stream| {
+        stream.write_all(&[IAC, DO, OPT_TTYPE, IAC, WILL, OPT_ECHO]).unwrap();
+        let mut raw = Vec::new();
+        let parsed = read_until(&mut stream, &mut raw, |parsed| {
+            parsed.subs.len() == 5 && parsed.commands.contains(&(WONT, OPT_TTYPE)) && parsed.commands.contains(&(DONT, OPT_ECHO))
+        });
+        for sub in &parsed.subs {
+            stream.write_all(&ack(sub, &sub[2..])).unwrap();
+        }
+        parsed
+    });
+    let started = Instant::now();
+    Rfc2217Port::open(&config(&addr, Parity::Even), 115_200).unwrap();
+    assert!(started.elapsed() < PORT_TIMEOUT, "подтверждение не дождались");
+
+    let parsed = handle.join().unwrap();
+    assert!(parsed.commands.contains(&(WILL, OPT_COM_PORT)));
+    assert_eq!(
+        parsed.subs,
+        [
+            [&[OPT_COM_PORT, 1][..], &115_200u32.to_be_bytes()].concat(),
+            vec![OPT_COM_PORT, 2, 8],
+            vec![OPT_COM_PORT, SET_PARITY, PARITY_EVEN],
+            vec![OPT_COM_PORT, 4, 1],
+            vec![OPT_COM_PORT, 5, 1],
+        ]
+    );
+}
+
+#[test]
+fn data_0xff_is_escaped_both_ways() {
+    let (addr, handle) = server(|mut stream| {
+        negotiate(&mut stream, |sub| sub[2..].to_vec());
+        // Между данными — удвоенный 0xFF и команда Telnet, которую клиент должен вырезать.
+        stream.write_all(&[0x10, IAC, IAC, 0x20, IAC, NOP, 0x30]).unwrap();
+        let mut raw = Vec::new();
+        read_until(&mut stream, &mut raw, |parsed| parsed.data.len() == 3);
+        raw
+    });
+    let mut port = Rfc2217Port::open(&config(&addr, Parity::None), 921_600).unwrap();
+    port.write_all(&[0x01, 0xFF, 0x02]).unwrap();
+    let mut data = [0u8; 4];
+    port.read_exact(&mut data).unwrap();
+    assert_eq!(data, [0x10, 0xFF, 0x20, 0x30]);
+    assert_eq!(handle.join().unwrap(), [0x01, IAC, IAC, 0x02]);
+}
+
+#[test]
+fn parity_the_server_does_not_set_is_rejected() {
+    let (addr, handle) = server(|mut stream| {
+        negotiate(&mut stream, |sub| if sub[1] == SET_PARITY { vec![PARITY_NONE] } else { sub[2..].to_vec() });
+    });
+    let result = Rfc2217Port::open(&config(&addr, Parity::Even), 115_200);
+    assert!(matches!(result, Err(Error::Policy(_))), "{:?}", result.err());
+    handle.join().unwrap();
+}
+
+#[test]
+fn silent_server_times_out() {
+    // Сервер принимает соединение и ничего не отвечает, пока клиент не уйдёт.
+    let silent = || {
+        server(|mut stream| {
+            let mut chunk = [0u8; 1024];
+            while matches!(stream.read(&mut chunk), Ok(n) if n > 0) {}
+        })
+    };
+
+    // Без чётности параметры по умолчанию подходят: после таймаута продолжаем с запрошенными.
+    let (addr, handle) = silent();
+    let started = Instant::now();
+    let port = Rfc2217Port::open(&config(&addr, Parity::None), 115_200).unwrap();
+    assert!(started.elapsed() >= PORT_TIMEOUT);
+    drop(port);
+    handle.join().unwrap();
+
+    // Чётность без подтверждения не включена, и каждый байт пришёл бы с ошибкой.
+    let (addr, handle) = silent();
+    let result = Rfc2217Port::open(&config(&addr, Parity::Even), 115_200);
+    assert!(matches!(result, Err(Error::Policy(_))), "{:?}", result.err());
+    handle.join().unwrap();
+}
+
+#[test]
+fn server_without_com_port_option_is_refused() {
+    let (addr, handle) = server(|mut stream| {
+        stream.write_all(&[IAC, DONT, OPT_COM_PORT]).unwrap();
+        let mut chunk = [0u8; 1024];
+        while matches!(stream.read(&mut chunk), Ok(n) if n > 0) {}
+    });
+    let result = Rfc2217Port::open(&config(&addr, Parity::None), 115_200);
+    assert!(matches!(result, Err(Error::Protocol(_))), "{:?}", result.err());
+    handle.join().unwrap();
+}
+
+#[test]
+fn endpoint_carries_the_line_settings() {
+    let endpoint = Endpoint::parse("rfc2217://ports.local:4001?parity=odd").unwrap();
+    assert_eq!(endpoint, Endpoint::Rfc2217(config("ports.local:4001", Parity::Odd)));
+    assert_eq!(endpoint.to_string(), "rfc2217://ports.local:4001?parity=odd");
+    assert!(matches!(Endpoint::parse("rfc2217://ports.local:4001?stop=2"), Err(Error::Policy(_))));
+    assert!(matches!(Endpoint::parse("rfc2217://ports.local:4001?parity=mark"), Err(Error::Policy(_))));
+
+    // Сервер, который закрыл соединение, — сбой транспорта, а не конец потока.
+    let (addr, handle) = server(|mut stream| {
+        negotiate(&mut stream, |sub| sub[2..].to_vec());
+    });
+    let mut port = Rfc2217Port::open(&config(&addr, Parity::None), 115_200).unwrap();
+    handle.join().unwrap();
+    let error = port.read(&mut [0u8; 16]).unwrap_err();
+    assert_eq!(error.kind(), io::ErrorKind::ConnectionAborted);
+}
@@ -0,0 +1,93 @@
+//! Режим спасения: невосстановленный файл сохраняется с нулями на месте потерь и картой повреждений.
+
+mod common;
+
+use std::fs;
+use std::path::PathBuf;
+use std::thread;
+
+use diode_core::port::PORT_TIMEOUT;
+use diode_core::protocol::FRAME_HEADER_LEN;
+use diode_core::transport::pipe;
+use diode_core::{send, DamagedRange, Event, Receiver, SendOptions};
+use common::{CorruptTx, Layout, TempDir};
+
+const FILE_LEN: usize = 2000;
+fn content() -> Vec<u8> {
+    (0..FILE_LEN as u32).map(|i| (i * 7 + 1) as u8).collect()
+}
+
+fn layout() -> Layout {
+    Layout::new(&SendOptions::default(), FILE_LEN as u64)
+}
+
+/// Принимает файл с испорченными байтами; возвращает событие и выходную директорию.
+fn receive(dir: &TempDir, flips: Vec<u64>, salvage: bool) -> (Event, PathBuf) {
+    let path = dir.0.join("data.bin");
+    fs::write(&path, content()).unwrap();
+    let (pipe_tx, pipe_rx) = pipe(PORT_TIMEOUT);
+    let mut tx = CorruptTx::new(pipe_tx, flips);
+    let sender = thread::spawn(move || send(&mut tx, &SendOptions::default(), path.to_str().unwrap()));
+    let out = dir.0.join("out");
+    let mut receiver = Receiver::new(Box::new(pipe_rx), &out.to_string_lossy());
+    receiver.set_salvage(salvage);
+    let event = loop {
+        if let Some(event) = receiver.poll().unwrap() {
+            break event;
+        }
+    };
+    sender.join().unwrap().unwrap();
+    (event, out)
+}
+
+#[test]
+fn unrecoverable_blocks_are_zero_filled_and_mapped() {
+    let dir = TempDir::new("blocks");
+    let layout = layout();
+    let flips = [3, 4, 7].into_iter().flat_map(|block| layout.ruin_block(block)).collect();
+    let (event, _) = receive(&dir, flips, true);
+    let block_len = layout.header.block_len as u64;
+    let Event::Received(file) = event else { panic!("файл не сохранён") };
+    let damage = file.damage.expect("файл должен быть частичным");
+    assert_eq!(
+        damage.ranges,
+        [
+            DamagedRange { units: 3..=4, bytes: 3 * block_len..5 * block_len },
+            DamagedRange { units: 7..=7, bytes: 7 * block_len..8 * block_len },
+        ]
+    );
+    assert_eq!(damage.damaged_bytes(), 3 * block_len);
+    assert!(file.path.contains("partial_"), "{}", file.path);
+
+    let mut expected = content();
+    for range in &damage.ranges {
+        expected[range.bytes.start as usize..range.bytes.end as usize].fill(0);
+    }
+    assert_eq!(fs::read(&file.path).unwrap(), expected);
+
+    let map = fs::read_to_string(&damage.path).unwrap();
+    assert!(map.contains("\n735 1225 3-4\n"), "{}", map);
+    assert!(map.contains("\n1715 1960 7\n"), "{}", map);
+    assert!(map.contains("Не восстановлено блоков: 3 из 9"), "{}", map);
+}
+
+#[test]
+fn without_salvage_the_file_is_dropped() {
+    let dir = TempDir::new("dropped");
+    let (event, out) = receive(&dir, layout().ruin_block(3), false);
+    assert!(matches!(event, Event::Failed(_)));
+    assert_eq!(fs::read_dir(out).unwrap().count(), 0);
+}
+
+#[test]
+fn complete_file_without_trailer_is_saved_unverified() {
+    let dir = TempDir::new("trailer");
+    // Двадцать ошибок в трейлере: его короткий код столько не исправляет.
+    let trailer = layout().trailer_start() + FRAME_HEADER_LEN as u64;
+    let (event, _) = receive(&dir, (0..20).map(|i| trailer + i * 3).collect(), true);
+    let Event::Received(file) = event else { panic!("файл не сохранён") };
+    let damage = file.damage.expect("файл должен быть частичным");
+    assert!(damage.ranges.is_empty());
+    assert!(damage.reason.contains("трейлер"), "{}", damage.reason);
+    assert_eq!(fs::read(&file.path).unwrap(), content());
+}
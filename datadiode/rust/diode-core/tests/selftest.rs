@@ -0,0 +1,66 @@
+//! Проверка линии через канал в памяти: PRBS, расписание перебора скоростей и отказ от неверных планов.
+
+use std::thread;
+use std::time::Duration;
+
+use diode_core::port::PORT_TIMEOUT;
+use diode_core::selftest::{prbs_block, Prbs31};
+use diode_core::transport::pipe;
+use diode_core::{receive_selftest, send_selftest, Error, SelfTestPlan, StepReport};
+
+fn run(plan: SelfTestPlan, start_baud: u32) -> Vec<StepReport> {
+    let (mut tx, rx) = pipe(PORT_TIMEOUT);
+    let sender = thread::spawn(move || send_selftest(&mut tx, &plan));
+    let reports = receive_selftest(Box::new(rx), start_baud).unwrap();
+    sender.join().unwrap().unwrap();
+    reports
+}
+
+#[test]
+fn prbs_is_maximal_length_sequence() {
+    // Регистр PRBS-31 возвращается в начальное состояние только через 2^31 - 1 бит;
+    // здесь достаточно, что первые байты не повторяются с коротким периодом и поровну единиц и нулей.
+    let mut prbs = Prbs31::new(1);
+    let bytes: Vec<u8> = (0..1 << 16).map(|_| prbs.next_byte()).collect();
+    let ones: u32 = bytes.iter().map(|b| b.count_ones()).sum();
+    let total = bytes.len() as f64 * 8.0;
+    assert!((ones as f64 / total - 0.5).abs() < 0.01);
+    assert_ne!(bytes[..4096], bytes[4096..8192]);
+    assert_ne!(prbs_block(0), prbs_block(1));
+    assert_eq!(Prbs31::new(0).next_byte(), Prbs31::new(1).next_byte());
+}
+
+#[test]
+fn clean_link_has_no_errors() {
+    let reports = run(SelfTestPlan { bauds: vec![2_000_000], step: Duration::from_millis(500) }, 2_000_000);
+    assert_eq!(reports.len(), 1);
+    let report = &reports[0];
+    assert!(report.frames > 100, "{}", report);
+    assert!(report.is_clean(), "{}", report);
+    assert_eq!(report.ber(), Some(0.0));
+}
+
+#[test]
+fn sweep_follows_the_schedule() {
+    let bauds = vec![1_000_000, 2_000_000, 4_000_000];
+    let reports = run(SelfTestPlan { bauds: bauds.clone(), step: Duration::from_millis(300) }, bauds[0]);
+    assert_eq!(reports.iter().map(|r| r.baud).collect::<Vec<_>>(), bauds);
+    for report in &reports {
+        assert!(report.is_clean(), "{}", report);
+    }
+    // Чем выше скорость, тем больше кадров за тот же шаг.
+    assert!(reports[0].frames < reports[1].frames && reports[1].frames < reports[2].frames);
+}
+
+#[test]
+fn invalid_plans_are_rejected() {
+    let (mut tx, _rx) = pipe(PORT_TIMEOUT);
+    for plan in [
+        SelfTestPlan { bauds: vec![], step: Duration::from_secs(1) },
+        SelfTestPlan { bauds: vec![115200; 17], step: Duration::from_secs(1) },
+        SelfTestPlan { bauds: vec![0], step: Duration::from_secs(1) },
+        SelfTestPlan { bauds: vec![115200], step: Duration::ZERO },
+    ] {
+        assert!(matches!(send_selftest(&mut tx, &plan), Err(Error::Policy(_))), "{:?}", plan);
+    }
+}
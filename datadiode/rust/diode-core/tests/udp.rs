@@ -0,0 +1,181 @@
+//! Транспорт с датаграммами: группы RS режутся на фрагменты по датаграмме, потерянный фрагмент
+//! восстанавливается как стирания.
+
+mod common;
+
+use std::fs;
+use std::io::{self, Write};
+use std::net::{Ipv4Addr, UdpSocket};
+use std::thread;
+use std::time::Duration;
+
+use diode_core::protocol::{FRAME_DATA, FRAME_HEADER_LEN};
+use diode_core::transport::{pipe, PipeTx};
+use diode_core::udp::{UdpRx, UdpTx, DEFAULT_DATAGRAM_LEN, MAX_DATAGRAM_LEN, MIN_DATAGRAM_LEN};
+use diode_core::{send, Error, Event, Receiver, SendOptions, Tx, UdpConfig};
+use common::TempDir;
+
+/// Транспорт с датаграммами поверх канала: каждая запись — один кадр, `lost` решает по типу
+/// и номеру кадра, пропадёт ли он.
+struct DatagramTx {
+    inner: PipeTx,
+    datagram_len: usize,
+    lost: fn(u8, u32) -> bool,
+}
+
+impl Write for DatagramTx {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        // Служебные поля кадра закодированы систематическим RS: тип и номер лежат как есть.
+        let seq = u32::from_be_bytes(buf[5..9].try_into().unwrap());
+        if !(self.lost)(buf[4], seq) {
+            self.inner.write_all(buf)?;
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl Tx for DatagramTx {
+    fn datagram_len(&self) -> Option<usize> {
+        Some(self.datagram_len)
+    }
+}
+
+#[test]
+fn datagram_without_room_for_data_is_rejected() {
+    let dir = TempDir::new("tiny");
+    let source = dir.0.join("data.bin");
+    fs::write(&source, [1u8; 100]).unwrap();
+    for datagram_len in [0, FRAME_HEADER_LEN - 1, FRAME_HEADER_LEN] {
+        let (inner, _rx) = pipe(Duration::from_secs(1));
+        let mut tx = DatagramTx { inner, datagram_len, lost: |_, _| false };
+        let result = send(&mut tx, &SendOptions::default(), source.to_str().unwrap());
+        assert!(matches!(result, Err(Error::Policy(_))), "{}: {:?}", datagram_len, result);
+    }
+}
+
+const DATAGRAM_LEN: usize = 1472;
+
+fn content(len: usize) -> Vec<u8> {
+    (0..len as u32).map(|i| (i * 29 + i / 500) as u8).collect()
+}
+
+/// Сколько фрагментов в полной группе при перемежении `interleave`.
+fn stride(interleave: usize) -> u32 {
+    (interleave * 255).div_ceil(DATAGRAM_LEN - FRAME_HEADER_LEN) as u32
+}
+
+/// Отправляет файл через `DatagramTx` с `--rs 64` и возвращает событие приёмника.
+fn transfer(dir: &TempDir, data: &[u8], interleave: u8, lost: fn(u8, u32) -> bool) -> Event {
+    let source = dir.0.join("data.bin");
+    fs::write(&source, data).unwrap();
+    let (inner, rx) = pipe(Duration::from_secs(1));
+    let mut tx = DatagramTx { inner, datagram_len: DATAGRAM_LEN, lost };
+    let options = SendOptions { rs_bytes: 64, interleave, ..SendOptions::default() };
+    send(&mut tx, &options, source.to_str().unwrap()).unwrap();
+    drop(tx);
+    let mut receiver = Receiver::new(Box::new(rx), &dir.0.join("out").to_string_lossy());
+    receiver.set_salvage(true);
+    loop {
+        match receiver.poll() {
+            Ok(Some(event)) => return event,
+            Ok(None) => {}
+            Err(e) => panic!("{}", e),
+        }
+    }
+}
+
+#[test]
+fn lost_fragment_is_recovered_when_interleave_spreads_it() {
+    // Перемежение 100: фрагмент задевает около 15 байтов каждого блока, RS с 64 проверочными
+    // байтами восстанавливает их как стирания.
+    let dir = TempDir::new("spread");
+    let data = content(100_000);
+    let Event::Received(file) = transfer(&dir, &data, 100, |kind, seq| kind == FRAME_DATA && seq % stride(100) == 1) else {
+        panic!("файл не принят")
+    };
+    assert!(file.damage.is_none());
+    assert_eq!(fs::read(&file.path).unwrap(), data);
+    assert!(file.stats.corrected_blocks > 0 && file.stats.erased_symbols > 0, "{}", file.stats);
+}
+
+#[test]
+fn lost_fragment_is_lost_when_interleave_is_shallow() {
+    // Перемежение 20: фрагмент — около 73 байтов каждого блока, больше проверочных байтов.
+    let dir = TempDir::new("shallow");
+    let Event::Received(file) = transfer(&dir, &content(100_000), 20, |kind, seq| kind == FRAME_DATA && seq == stride(20) * 3 + 1)
+    else {
+        panic!("файл не принят")
+    };
+    let damage = file.damage.expect("файл должен быть частичным");
+    assert_eq!(damage.gap.missing, [60..=79]);
+}
+
+#[test]
+fn group_evicted_with_lost_fragments_is_reported_missing() {
+    // От группы 2 пришёл только последний фрагмент; за ней идут ещё больше `MAX_PARTIAL_GROUPS`
+    // групп, так что её вытесняют и декодируют задолго до конца копии.
+    let dir = TempDir::new("evicted");
+    let data = content(100_000);
+    let Event::Received(file) = transfer(&dir, &data, 20, |kind, seq| kind == FRAME_DATA && (8..11).contains(&seq)) else {
+        panic!("файл не принят")
+    };
+    let damage = file.damage.expect("файл должен быть частичным");
+    assert_eq!(damage.gap.missing, [40..=59]);
+    let block_len = 255 - 64;
+    let saved = fs::read(&file.path).unwrap();
+    assert_eq!(saved[..40 * block_len], data[..40 * block_len]);
+    assert_eq!(saved[60 * block_len..], data[60 * block_len..]);
+}
+
+#[test]
+fn udp_config_parses_and_checks_the_datagram_size() {
+    let config = UdpConfig::parse("127.0.0.1:5000").unwrap();
+    assert_eq!(config.addr, "127.0.0.1:5000".parse().unwrap());
+    assert_eq!((config.datagram_len, config.rate, config.iface, config.ttl), (DEFAULT_DATAGRAM_LEN, None, Ipv4Addr::UNSPECIFIED, 1));
+
+    let config = UdpConfig::parse("239.1.2.3:6000?size=9000&rate=100000000&iface=10.0.0.1&ttl=4").unwrap();
+    assert_eq!(config.datagram_len, 9000);
+    assert_eq!(config.rate, Some(100_000_000));
+    assert_eq!(config.iface, Ipv4Addr::new(10, 0, 0, 1));
+    assert_eq!(config.ttl, 4);
+
+    for spec in [
+        format!("127.0.0.1:5000?size={}", MIN_DATAGRAM_LEN - 1),
+        format!("127.0.0.1:5000?size={}", MAX_DATAGRAM_LEN + 1),
+        "127.0.0.1:5000?size=big".to_string(),
+        "127.0.0.1:5000?mtu=1500".to_string(),
+        "127.0.0.1".to_string(),
+    ] {
+        assert!(matches!(UdpConfig::parse(&spec), Err(Error::Policy(_))), "{}", spec);
+    }
+    assert_eq!(UdpConfig::parse(&format!("127.0.0.1:5000?size={}", MIN_DATAGRAM_LEN)).unwrap().datagram_len, MIN_DATAGRAM_LEN);
+}
+
+#[test]
+fn loopback_round_trip() {
+    let dir = TempDir::new("loopback");
+    let data = content(50_000);
+    let source = dir.0.join("data.bin");
+    fs::write(&source, &data).unwrap();
+    let port = UdpSocket::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+    let config = UdpConfig::parse(&format!("127.0.0.1:{}?size=1200&rate=50000000", port)).unwrap();
+
+    let rx = UdpRx::open(&config).unwrap();
+    let mut receiver = Receiver::new(Box::new(rx), &dir.0.join("out").to_string_lossy());
+    let mut tx = UdpTx::open(&config, 921600).unwrap();
+    let sender = thread::spawn(move || send(&mut tx, &SendOptions::default(), source.to_str().unwrap()));
+    let event = loop {
+        match receiver.poll() {
+            Ok(Some(event)) => break event,
+            Ok(None) => {}
+            Err(e) => panic!("{}", e),
+        }
+    };
+    sender.join().unwrap().unwrap();
+    let Event::Received(file) = event else { panic!("файл не принят") };
+    assert_eq!(fs::read(&file.path).unwrap(), data);
+}
@@ -0,0 +1,223 @@
+//! Модель линии: воспроизводимые по зерну искажения потока байтов.
+
+use std::fmt;
+use std::io::{self, Write};
+use std::thread;
+use std::time::Duration;
+
+use diode_core::Tx;
+
+/// Какие искажения и с какой частотой вносит линия. Частоты — вероятности на бит (`ber`)
+/// или на байт (остальные); 0 отключает искажение.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Impairments {
+    /// Вероятность инверсии каждого бита.
+    pub ber: f64,
+    /// Вероятность начала пачки ошибок на байте.
+    pub burst_rate: f64,
+    /// Длина пачки в байтах: каждый байт пачки заменяется случайным.
+    pub burst_len: usize,
+    pub drop_rate: f64,
+    pub duplicate_rate: f64,
+    /// Вероятность паузы перед байтом.
+    pub stall_rate: f64,
+    pub stall: Duration,
+    pub seed: u64,
+}
+
+impl Default for Impairments {
+    fn default() -> Self {
+        Impairments {
+            ber: 0.0,
+            burst_rate: 0.0,
+            burst_len: 16,
+            drop_rate: 0.0,
+            duplicate_rate: 0.0,
+            stall_rate: 0.0,
+            stall: Duration::from_secs(3),
+            seed: 1,
+        }
+    }
+}
+
+/// Генератор псевдослучайных чисел splitmix64: одинаковая последовательность на любой платформе.
+#[derive(Debug, Clone)]
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Rng(seed)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Равномерное число в (0, 1].
+    fn unit(&mut self) -> f64 {
+        ((self.next_u64() >> 11) + 1) as f64 / (1u64 << 53) as f64
+    }
+
+    /// Сколько испытаний пройдёт до следующего события с вероятностью `p`: геометрическое
+    /// распределение, чтобы редкие события не требовали случайного числа на каждый бит.
+    fn gap(&mut self, p: f64) -> u64 {
+        if p <= 0.0 {
+            u64::MAX
+        } else if p >= 1.0 {
+            0
+        } else {
+            (self.unit().ln() / (1.0 - p).ln()).floor().min(u64::MAX as f64) as u64
+        }
+    }
+}
+
+/// Что линия сделала с потоком.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Stats {
+    pub bytes_in: u64,
+    pub bytes_out: u64,
+    pub flipped_bits: u64,
+    pub bursts: u64,
+    pub dropped: u64,
+    pub duplicated: u64,
+    pub stalls: u64,
+}
+
+impl fmt::Display for Stats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "байт на входе {}, на выходе {}, инвертировано бит {}, пачек ошибок {}, выброшено байт {}, повторено байт {}, пауз {}",
+            self.bytes_in, self.bytes_out, self.flipped_bits, self.bursts, self.dropped, self.duplicated, self.stalls
+        )
+    }
+}
+
+/// Линия с искажениями. Одинаковые `Impairments` и одинаковый входной поток дают одинаковый
+/// выход независимо от того, какими кусками поток подаётся.
+pub struct Channel {
+    impairments: Impairments,
+    rng: Rng,
+    /// Через сколько бит следующая инверсия.
+    next_flip: u64,
+    /// Через сколько байт следующее событие каждого вида.
+    next_burst: u64,
+    next_drop: u64,
+    next_duplicate: u64,
+    next_stall: u64,
+    burst_left: usize,
+    stats: Stats,
+}
+
+/// Отсчитывает байт до события: `true`, если событие пришлось на этот байт.
+fn tick(counter: &mut u64, rng: &mut Rng, p: f64) -> bool {
+    if *counter == 0 {
+        *counter = rng.gap(p);
+        true
+    } else {
+        *counter -= 1;
+        false
+    }
+}
+
+impl Channel {
+    pub fn new(impairments: Impairments) -> Self {
+        let mut rng = Rng::new(impairments.seed);
+        Channel {
+            next_flip: rng.gap(impairments.ber),
+            next_burst: rng.gap(impairments.burst_rate),
+            next_drop: rng.gap(impairments.drop_rate),
+            next_duplicate: rng.gap(impairments.duplicate_rate),
+            next_stall: rng.gap(impairments.stall_rate),
+            burst_left: 0,
+            stats: Stats::default(),
+            rng,
+            impairments,
+        }
+    }
+
+    pub fn stats(&self) -> &Stats {
+        &self.stats
+    }
+
+    /// Пропускает `input` через линию и пишет результат в `out`. На паузах уже накопленное
+    /// отправляется, и поток останавливается на `Impairments::stall`.
+    pub fn process<W: Write + ?Sized>(&mut self, input: &[u8], out: &mut W) -> io::Result<()> {
+        let mut buf = Vec::with_capacity(input.len() + input.len() / 16);
+        for &byte in input {
+            self.stats.bytes_in += 1;
+            if tick(&mut self.next_stall, &mut self.rng, self.impairments.stall_rate) {
+                out.write_all(&buf)?;
+                out.flush()?;
+                self.stats.bytes_out += buf.len() as u64;
+                buf.clear();
+                self.stats.stalls += 1;
+                thread::sleep(self.impairments.stall);
+            }
+            if tick(&mut self.next_drop, &mut self.rng, self.impairments.drop_rate) {
+                self.stats.dropped += 1;
+                continue;
+            }
+
+            let mut byte = byte;
+            if tick(&mut self.next_burst, &mut self.rng, self.impairments.burst_rate) && self.impairments.burst_len > 0 {
+                self.stats.bursts += 1;
+                self.burst_left = self.impairments.burst_len;
+            }
+            if self.burst_left > 0 {
+                self.burst_left -= 1;
+                let noise = self.rng.next_u64() as u8;
+                self.stats.flipped_bits += noise.count_ones() as u64;
+                byte ^= noise;
+            }
+            while self.next_flip < 8 {
+                byte ^= 1 << self.next_flip;
+                self.stats.flipped_bits += 1;
+                self.next_flip = self.next_flip.saturating_add(1).saturating_add(self.rng.gap(self.impairments.ber));
+            }
+            self.next_flip -= 8;
+
+            buf.push(byte);
+            if tick(&mut self.next_duplicate, &mut self.rng, self.impairments.duplicate_rate) {
+                self.stats.duplicated += 1;
+                buf.push(byte);
+            }
+        }
+        self.stats.bytes_out += buf.len() as u64;
+        out.write_all(&buf)
+    }
+}
+
+/// Передающий транспорт, пропускающий всё записанное через линию с искажениями.
+/// Линия моделирует поток UART, поэтому и сам транспорт — поток без границ датаграмм.
+pub struct ImpairedTx<W> {
+    inner: W,
+    channel: Channel,
+}
+
+impl<W: Write> ImpairedTx<W> {
+    pub fn new(inner: W, impairments: Impairments) -> Self {
+        ImpairedTx { inner, channel: Channel::new(impairments) }
+    }
+
+    pub fn stats(&self) -> &Stats {
+        self.channel.stats()
+    }
+}
+
+impl<W: Write> Write for ImpairedTx<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.channel.process(buf, &mut self.inner)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl<W: Write + Send> Tx for ImpairedTx<W> {}
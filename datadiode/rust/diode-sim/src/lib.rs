@@ -0,0 +1,30 @@
+//! Имитатор линии для проверки кодов коррекции и ресинхронизации: встаёт между транспортами
+//! отправителя и приёмника и вносит воспроизводимые по зерну искажения — инверсии битов,
+//! пачки ошибок, потерю и повтор байтов, паузы.
+
+pub mod channel;
+pub mod trial;
+
+use std::io;
+
+use diode_core::{Error, Result, Rx, Tx};
+
+pub use channel::{Channel, ImpairedTx, Impairments, Rng, Stats};
+pub use trial::{trial, TrialOutcome};
+
+/// Переносит поток из `rx` в `tx` через линию, пока источник не закончится.
+/// Паузы в источнике не прерывают работу: так имитатор стоит между двумя портами сколько угодно.
+pub fn relay(rx: &mut dyn Rx, tx: &mut dyn Tx, channel: &mut Channel) -> Result<()> {
+    let mut buf = [0u8; 4096];
+    loop {
+        let n = match rx.read(&mut buf) {
+            Ok(0) => break,
+            Ok(n) => n,
+            Err(e) if e.kind() == io::ErrorKind::TimedOut => continue,
+            Err(e) => return Err(Error::io("Ошибка чтения")(e)),
+        };
+        channel.process(&buf[..n], tx).map_err(Error::io("Ошибка записи"))?;
+        tx.flush().map_err(Error::io("Ошибка записи"))?;
+    }
+    tx.flush().map_err(Error::io("Ошибка записи"))
+}
@@ -0,0 +1,151 @@
+use std::time::Duration;
+
+use clap::{Args, Parser, Subcommand, ValueEnum};
+
+use diode_sim::{Channel, Impairments};
+
+#[derive(Parser)]
+#[command(about = "Имитатор линии с искажениями между отправителем и приёмником")]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Переносит поток из одного транспорта в другой, внося искажения
+    Relay {
+        /// Откуда читать: последовательный порт, `file:ПУТЬ`, `udp://ХОСТ:ПОРТ`, `rfc2217://ХОСТ:ПОРТ` или `-` для stdin
+        #[arg(long, default_value = "-")]
+        from: String,
+        /// Куда писать, в том же виде; `-` — stdout
+        #[arg(long, default_value = "-")]
+        to: String,
+        #[arg(long, default_value_t = 921600)]
+        baud: u32,
+        #[command(flatten)]
+        impairments: ImpairmentArgs,
+    },
+    /// Прогоняет передачу файла через линию несколько раз с разными зёрнами и считает успехи
+    Trial {
+        #[arg(long)]
+        file: String,
+        #[arg(long, default_value_t = 10)]
+        rs: u8,
+        #[arg(long, default_value_t = 1)]
+        interleave: u8,
+        #[arg(long, value_enum, default_value_t = Fec::Rs)]
+        fec: Fec,
+        /// Избыточность RaptorQ в процентах
+        #[arg(long, default_value_t = diode_core::DEFAULT_REPAIR_PERCENT)]
+        overhead: u8,
+        #[arg(long, default_value_t = 1)]
+        repeat: u8,
+        /// Сколько прогонов; зерно каждого следующего на единицу больше
+        #[arg(long, default_value_t = 10)]
+        runs: u64,
+        /// Куда принимать файлы во время прогонов
+        #[arg(long, default_value = "sim_received/")]
+        dir: String,
+        #[command(flatten)]
+        impairments: ImpairmentArgs,
+    },
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Fec {
+    Rs,
+    Raptorq,
+}
+
+#[derive(Args)]
+struct ImpairmentArgs {
+    /// Вероятность инверсии бита
+    #[arg(long, default_value_t = 0.0)]
+    ber: f64,
+    /// Вероятность начала пачки ошибок на байте
+    #[arg(long, default_value_t = 0.0)]
+    burst_rate: f64,
+    /// Длина пачки ошибок, байт
+    #[arg(long, default_value_t = 16)]
+    burst_len: usize,
+    /// Вероятность потери байта
+    #[arg(long, default_value_t = 0.0)]
+    drop_rate: f64,
+    /// Вероятность повтора байта
+    #[arg(long, default_value_t = 0.0)]
+    dup_rate: f64,
+    /// Вероятность паузы перед байтом
+    #[arg(long, default_value_t = 0.0)]
+    stall_rate: f64,
+    /// Длительность паузы, мс
+    #[arg(long, default_value_t = 3000)]
+    stall_ms: u64,
+    #[arg(long, default_value_t = 1)]
+    seed: u64,
+}
+
+impl ImpairmentArgs {
+    fn impairments(&self) -> Impairments {
+        Impairments {
+            ber: self.ber,
+            burst_rate: self.burst_rate,
+            burst_len: self.burst_len,
+            drop_rate: self.drop_rate,
+            duplicate_rate: self.dup_rate,
+            stall_rate: self.stall_rate,
+            stall: Duration::from_millis(self.stall_ms),
+            seed: self.seed,
+        }
+    }
+}
+
+fn run(cli: Cli) -> diode_core::Result<()> {
+    match cli.command {
+        Command::Relay { from, to, baud, impairments } => {
+            let mut rx = diode_core::Endpoint::parse(&from)?.open_rx(baud)?;
+            let mut tx = diode_core::Endpoint::parse(&to)?.open_tx(baud)?;
+            let mut channel = Channel::new(impairments.impairments());
+            let result = diode_sim::relay(&mut *rx, &mut *tx, &mut channel);
+            eprintln!("Линия: {}", channel.stats());
+            result
+        }
+        Command::Trial { file, rs, interleave, fec, overhead, repeat, runs, dir, impairments } => {
+            let options = diode_core::SendOptions {
+                rs_bytes: rs,
+                interleave,
+                fec: match fec {
+                    Fec::Rs => diode_core::FecMode::ReedSolomon,
+                    Fec::Raptorq => diode_core::FecMode::RaptorQ { repair_percent: overhead },
+                },
+                repeat,
+                checkpoint: None,
+            };
+            let base = impairments.impairments();
+            let mut received = 0;
+            for run in 0..runs {
+                let impairments = Impairments { seed: base.seed.wrapping_add(run), ..base.clone() };
+                let outcome = diode_sim::trial(&file, &options, &impairments, std::path::Path::new(&dir))?;
+                match (&outcome.error, &outcome.decode) {
+                    (None, decode) => {
+                        received += 1;
+                        println!("Прогон {} (зерно {}): принят; {}", run + 1, impairments.seed, outcome.stats);
+                        if let Some(decode) = decode {
+                            println!("    коррекция: {}", decode);
+                        }
+                    }
+                    (Some(e), _) => println!("Прогон {} (зерно {}): не принят: {}; {}", run + 1, impairments.seed, e, outcome.stats),
+                }
+            }
+            println!("Принято {} из {}", received, runs);
+            Ok(())
+        }
+    }
+}
+
+fn main() {
+    if let Err(e) = run(Cli::parse()) {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
+}
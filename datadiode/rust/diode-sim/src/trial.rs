@@ -0,0 +1,63 @@
+//! Прогон передачи через линию с искажениями в одном процессе: настоящие отправитель
+//! и приёмник соединены каналом в памяти.
+
+use std::fs;
+use std::path::Path;
+use std::thread;
+
+use diode_core::port::PORT_TIMEOUT;
+use diode_core::transport::pipe;
+use diode_core::{send, DecodeStats, Error, Event, Receiver, Result, SendOptions};
+
+use crate::channel::{ImpairedTx, Impairments, Stats};
+
+/// Итог одного прогона.
+#[derive(Debug)]
+pub struct TrialOutcome {
+    /// Файл принят и его SHA-256 совпал с присланным.
+    pub received: bool,
+    /// Почему файл не принят.
+    pub error: Option<String>,
+    pub stats: Stats,
+    /// Работа кода коррекции, если файл принят.
+    pub decode: Option<DecodeStats>,
+}
+
+/// Отправляет `file_path` с параметрами `options` через линию `impairments` и принимает его
+/// в `output_dir`. Принятый файл удаляется: важен только итог.
+pub fn trial(file_path: &str, options: &SendOptions, impairments: &Impairments, output_dir: &Path) -> Result<TrialOutcome> {
+    let (pipe_tx, pipe_rx) = pipe(PORT_TIMEOUT);
+    let mut tx = ImpairedTx::new(pipe_tx, impairments.clone());
+    let path = file_path.to_string();
+    let options = options.clone();
+    let sender = thread::spawn(move || {
+        let result = send(&mut tx, &options, &path);
+        (result, tx.stats().clone())
+    });
+
+    // Прогон не продолжают после перезапуска: всё принятое удаляется вместе с приёмником.
+    let mut receiver = Receiver::new(Box::new(pipe_rx), &output_dir.to_string_lossy());
+    receiver.set_journal(false);
+    // Пауза дольше таймаута порта откладывает передачу, но отправитель продолжит её сам:
+    // итог прогона — следующее событие.
+    let received = loop {
+        match receiver.poll() {
+            Ok(Some(Event::Received(file))) => break Ok(file),
+            Ok(Some(Event::Failed(e))) => break Err(e.to_string()),
+            Ok(Some(Event::Suspended(_)) | None) => {}
+            Err(Error::Closed) => break Err("Заголовок передачи не принят".to_string()),
+            Err(e) => return Err(e),
+        }
+    };
+    // Отправитель может ещё дописывать лишние копии: канал в памяти не ограничен, он их примет.
+    let (sent, stats) = sender.join().expect("поток отправителя завершился аварийно");
+    sent?;
+
+    Ok(match received {
+        Ok(file) => {
+            let _ = fs::remove_file(&file.path);
+            TrialOutcome { received: true, error: None, stats, decode: Some(file.stats) }
+        }
+        Err(error) => TrialOutcome { received: false, error: Some(error), stats, decode: None },
+    })
+}
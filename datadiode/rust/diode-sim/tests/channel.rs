@@ -0,0 +1,150 @@
+//! Проверки модели линии и прогонов передачи через неё.
+
+mod common;
+
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+
+use diode_core::port::PORT_TIMEOUT;
+use diode_core::SendOptions;
+use diode_sim::{trial, Channel, Impairments, Rng};
+use common::TempDir;
+
+fn pattern(len: usize, seed: u64) -> Vec<u8> {
+    let mut rng = Rng::new(seed);
+    (0..len).map(|_| rng.next_u64() as u8).collect()
+}
+
+fn run(impairments: &Impairments, input: &[u8], chunk: usize) -> (Vec<u8>, diode_sim::Stats) {
+    let mut channel = Channel::new(impairments.clone());
+    let mut out = Vec::new();
+    for part in input.chunks(chunk) {
+        channel.process(part, &mut out).unwrap();
+    }
+    (out, channel.stats().clone())
+}
+
+/// После прогона в выходной директории ничего не остаётся: ни принятого файла, ни временных.
+fn assert_clean(out: &Path) {
+    let leftovers: Vec<_> = fs::read_dir(out).map(|dir| dir.map(|e| e.unwrap().file_name()).collect()).unwrap_or_default();
+    assert!(leftovers.is_empty(), "после прогона в {} остались файлы: {:?}", out.display(), leftovers);
+}
+
+fn faulty() -> Impairments {
+    Impairments {
+        ber: 1e-3,
+        burst_rate: 1e-4,
+        drop_rate: 1e-4,
+        duplicate_rate: 1e-4,
+        seed: 42,
+        ..Impairments::default()
+    }
+}
+
+#[test]
+fn clean_channel_is_transparent() {
+    let input = pattern(10_000, 1);
+    let (out, stats) = run(&Impairments::default(), &input, 777);
+    assert_eq!(out, input);
+    assert_eq!(stats.bytes_out, input.len() as u64);
+}
+
+#[test]
+fn same_seed_gives_same_output_regardless_of_chunking() {
+    let input = pattern(200_000, 2);
+    let (a, stats_a) = run(&faulty(), &input, 4096);
+    let (b, stats_b) = run(&faulty(), &input, 13);
+    assert_eq!(a, b);
+    assert_eq!(stats_a, stats_b);
+
+    let (c, _) = run(&Impairments { seed: 43, ..faulty() }, &input, 4096);
+    assert_ne!(a, c);
+}
+
+#[test]
+fn counts_match_output() {
+    let input = pattern(500_000, 3);
+    let (out, stats) = run(&faulty(), &input, 4096);
+    assert_eq!(stats.bytes_in, input.len() as u64);
+    assert_eq!(stats.bytes_out, out.len() as u64);
+    assert_eq!(out.len() as u64, stats.bytes_in - stats.dropped + stats.duplicated);
+    assert!(stats.dropped > 0 && stats.duplicated > 0 && stats.bursts > 0);
+}
+
+#[test]
+fn bit_error_rate_matches() {
+    let input = vec![0u8; 1_000_000];
+    let ber = 1e-3;
+    let (out, stats) = run(&Impairments { ber, seed: 7, ..Impairments::default() }, &input, 4096);
+    let flipped: u64 = out.iter().map(|b| b.count_ones() as u64).sum();
+    assert_eq!(flipped, stats.flipped_bits);
+    let expected = ber * input.len() as f64 * 8.0;
+    assert!((flipped as f64 - expected).abs() < expected * 0.05, "инвертировано {}, ожидалось около {}", flipped, expected);
+}
+
+#[test]
+fn stalls_pause_the_stream() {
+    let input = pattern(1000, 4);
+    let impairments = Impairments { stall_rate: 2e-3, stall: Duration::from_millis(10), seed: 5, ..Impairments::default() };
+    let started = std::time::Instant::now();
+    let (out, stats) = run(&impairments, &input, 100);
+    assert_eq!(out, input);
+    assert!(stats.stalls > 0);
+    assert!(started.elapsed() >= impairments.stall * stats.stalls as u32);
+}
+
+#[test]
+fn trial_separates_weak_and_strong_rs() {
+    let dir = TempDir::new("trial");
+    let path = dir.0.join("data.bin");
+    fs::write(&path, pattern(100_000, 6)).unwrap();
+    let impairments = Impairments { ber: 1e-3, seed: 9, ..Impairments::default() };
+
+    let clean = trial(path.to_str().unwrap(), &SendOptions::default(), &Impairments::default(), &dir.0.join("out")).unwrap();
+    assert!(clean.received, "{:?}", clean.error);
+    assert_eq!(clean.decode.unwrap().corrected_symbols, 0);
+
+    let weak = SendOptions { rs_bytes: 2, ..SendOptions::default() };
+    let outcome = trial(path.to_str().unwrap(), &weak, &impairments, &dir.0.join("out")).unwrap();
+    assert!(!outcome.received);
+    assert!(outcome.stats.flipped_bits > 0);
+    assert_clean(&dir.0.join("out"));
+
+    let strong = SendOptions { rs_bytes: 48, interleave: 4, ..SendOptions::default() };
+    let outcome = trial(path.to_str().unwrap(), &strong, &impairments, &dir.0.join("out")).unwrap();
+    assert!(outcome.received, "{:?}", outcome.error);
+    let decode = outcome.decode.unwrap();
+    assert!(decode.corrected_symbols > 0 && decode.worst_block.unwrap().1 <= decode.correctable, "{}", decode);
+    assert_clean(&dir.0.join("out"));
+}
+
+#[test]
+fn trial_survives_a_stall_longer_than_the_port_timeout() {
+    let dir = TempDir::new("stall");
+    let path = dir.0.join("data.bin");
+    fs::write(&path, pattern(50_000, 10)).unwrap();
+    // Одна пауза в первой копии: приёмник откладывает передачу, а продолжает её заголовком
+    // второй копии, которая и восполняет пропущенное за паузу.
+    let stall = PORT_TIMEOUT + Duration::from_millis(500);
+    let impairments = Impairments { stall_rate: 2e-5, stall, seed: 7, ..Impairments::default() };
+    let options = SendOptions { repeat: 2, ..SendOptions::default() };
+    let outcome = trial(path.to_str().unwrap(), &options, &impairments, &dir.0.join("out")).unwrap();
+    assert_eq!(outcome.stats.stalls, 1);
+    assert!(outcome.received, "{:?}", outcome.error);
+    assert_clean(&dir.0.join("out"));
+}
+
+#[test]
+fn interrupted_trial_leaves_nothing_behind() {
+    let dir = TempDir::new("interrupted");
+    let path = dir.0.join("data.bin");
+    fs::write(&path, pattern(50_000, 12)).unwrap();
+    // Пауза в единственной копии: продолжения нет, и передача откладывается до конца потока.
+    let stall = PORT_TIMEOUT + Duration::from_millis(500);
+    let impairments = Impairments { stall_rate: 2e-5, stall, seed: 7, ..Impairments::default() };
+    let outcome = trial(path.to_str().unwrap(), &SendOptions::default(), &impairments, &dir.0.join("out")).unwrap();
+    assert_eq!(outcome.stats.stalls, 1);
+    assert!(!outcome.received);
+    assert_clean(&dir.0.join("out"));
+}
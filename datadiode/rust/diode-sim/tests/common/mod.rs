@@ -0,0 +1,23 @@
+//! Общее для тестов крейта.
+
+use std::fs;
+use std::path::PathBuf;
+
+/// Временная директория теста, удаляется вместе со всем содержимым. Имя тестового
+/// файла в пути разводит одноимённые директории разных тестов.
+pub struct TempDir(pub PathBuf);
+
+impl TempDir {
+    pub fn new(name: &str) -> Self {
+        let path = std::env::temp_dir().join(format!("diode-sim-{}-{}-{}", env!("CARGO_CRATE_NAME"), name, std::process::id()));
+        let _ = fs::remove_dir_all(&path);
+        fs::create_dir_all(&path).unwrap();
+        TempDir(path)
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
@@ -0,0 +1,46 @@
+//! Проверка линии через имитатор: измеренные ошибки сходятся с тем, что линия внесла.
+
+use std::thread;
+use std::time::Duration;
+
+use diode_core::port::PORT_TIMEOUT;
+use diode_core::transport::pipe;
+use diode_core::{receive_selftest, send_selftest, SelfTestPlan, StepReport};
+use diode_sim::{ImpairedTx, Impairments, Stats};
+
+fn run(impairments: Impairments) -> (StepReport, Stats) {
+    let (pipe_tx, rx) = pipe(PORT_TIMEOUT);
+    let mut tx = ImpairedTx::new(pipe_tx, impairments);
+    let plan = SelfTestPlan { bauds: vec![4_000_000], step: Duration::from_secs(1) };
+    let sender = thread::spawn(move || {
+        send_selftest(&mut tx, &plan).unwrap();
+        tx.stats().clone()
+    });
+    let mut reports = receive_selftest(Box::new(rx), 4_000_000).unwrap();
+    let stats = sender.join().unwrap();
+    assert_eq!(reports.len(), 1);
+    (reports.remove(0), stats)
+}
+
+#[test]
+fn measured_ber_matches_the_line() {
+    let ber = 1e-4;
+    let (report, stats) = run(Impairments { ber, seed: 11, ..Impairments::default() });
+    assert!(report.bit_errors > 0 && report.bit_errors <= stats.flipped_bits, "{}; {}", report, stats);
+    let measured = report.ber().unwrap();
+    assert!((measured - ber).abs() < ber * 0.3, "{}", report);
+    assert_eq!(report.lost_bytes, 0);
+    assert_eq!(report.extra_bytes, 0);
+}
+
+#[test]
+fn slips_are_counted_as_lost_and_extra_bytes() {
+    let (report, stats) = run(Impairments { drop_rate: 2e-5, duplicate_rate: 2e-5, seed: 12, ..Impairments::default() });
+    assert!(stats.dropped > 0 && stats.duplicated > 0, "{}", stats);
+    // Без поиска сдвига каждый сдвиг дал бы сотни ошибочных бит до конца кадра; лишний байт
+    // у самого конца кадра неотличим от шума и остаётся парой ошибок бит.
+    let slips = stats.dropped + stats.duplicated;
+    assert!(report.bit_errors < 16 * slips, "{}; {}", report, stats);
+    assert!(report.lost_bytes >= stats.dropped, "{}; {}", report, stats);
+    assert!(report.extra_bytes <= stats.duplicated && report.extra_bytes + 2 >= stats.duplicated, "{}; {}", report, stats);
+}
@@ -1,6 +1,7 @@
 use iced::{Application, Settings, Element, Command, Subscription};
 use rfd::FileDialog;
 use super::Args;
+use std::sync::Arc;
 use std::time::Instant;
 
 #[derive(Default)]
@@ -8,25 +9,39 @@ pub struct SenderApp {
     port: String,
     baud: u32,
     rs: u8,
+    interleave: u8,
+    raptorq: bool,
+    overhead: u8,
+    repeat: u8,
     file_path: Option<String>,
     status: String,
     port_input: String,
     baud_input: String,
     rs_input: String,
+    interleave_input: String,
+    overhead_input: String,
+    repeat_input: String,
     status_opacity: f32,
     status_set_time: Option<Instant>,
 }
 
 impl SenderApp {
     pub fn run_gui(args: Args) {
-        let initial_port = args.port.unwrap_or_else(|| super::core::find_com_port().unwrap_or("COM14".to_string()));
+        let (initial_raptorq, initial_overhead) = match args.fec_mode() {
+            diode_core::FecMode::RaptorQ { repair_percent } => (true, repair_percent),
+            diode_core::FecMode::ReedSolomon => (false, diode_core::DEFAULT_REPAIR_PERCENT),
+        };
+        let initial_port = args.port.unwrap_or_else(|| diode_core::find_com_port().unwrap_or("COM14".to_string()));
         let initial_baud = args.baud.unwrap_or(921600);
-        let initial_rs = args.rs.unwrap_or(10);
+        let defaults = diode_core::SendOptions::default();
+        let initial_rs = args.rs.unwrap_or(defaults.rs_bytes);
+        let initial_interleave = args.interleave.unwrap_or(defaults.interleave);
+        let initial_repeat = args.repeat.unwrap_or(defaults.repeat);
         let initial_file = args.file;
 
         let settings = Settings {
             window: iced::window::Settings {
-                size: (650, 300),
+                size: (650, 420),
                 resizable: true,
                 ..iced::window::Settings::default()
             },
@@ -34,11 +49,18 @@ impl SenderApp {
                 port: initial_port,
                 baud: initial_baud,
                 rs: initial_rs,
+                interleave: initial_interleave,
+                raptorq: initial_raptorq,
+                overhead: initial_overhead,
+                repeat: initial_repeat,
                 file_path: initial_file,
                 status: String::new(),
                 port_input: String::new(),
                 baud_input: String::new(),
                 rs_input: String::new(),
+                interleave_input: String::new(),
+                overhead_input: String::new(),
+                repeat_input: String::new(),
                 status_opacity: 1.0,
                 status_set_time: None,
             },
@@ -46,6 +68,20 @@ impl SenderApp {
         };
         SenderApp::run(settings).unwrap();
     }
+
+    fn send_options(&self) -> diode_core::SendOptions {
+        diode_core::SendOptions {
+            rs_bytes: self.rs,
+            interleave: self.interleave,
+            fec: if self.raptorq {
+                diode_core::FecMode::RaptorQ { repair_percent: self.overhead }
+            } else {
+                diode_core::FecMode::ReedSolomon
+            },
+            repeat: self.repeat,
+            checkpoint: None,
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -53,12 +89,19 @@ pub enum Message {
     SetPort,
     SetBaud,
     SetRs,
+    SetInterleave,
+    SetOverhead,
+    SetRepeat,
+    FecToggled(bool),
     PortChanged(String),
     BaudChanged(String),
     RsChanged(String),
+    InterleaveChanged(String),
+    OverheadChanged(String),
+    RepeatChanged(String),
     SelectFile,
     SendFile,
-    FileSent(Result<(), String>),
+    FileSent(Result<(), Arc<diode_core::Error>>),
     Tick,
 }
 
@@ -73,12 +116,12 @@ fn new(flags: Self::Flags) -> (Self, Command<Message>) {
     if app.file_path.is_some() {
         let port = app.port.clone();
         let baud = app.baud;
-        let rs = app.rs;
+        let options = app.send_options();
         let file_path = app.file_path.clone().unwrap();
         return (
             app,
             Command::perform(
-                async move { super::core::send_file(&port, baud, rs, &file_path) },
+                async move { diode_core::send_file(&port, baud, &options, &file_path).map_err(Arc::new) },
                 Message::FileSent,
             ),
         );
@@ -122,9 +165,45 @@ fn new(flags: Self::Flags) -> (Self, Command<Message>) {
                 self.status_opacity = 1.0;
                 self.status_set_time = Some(Instant::now());
             }
+            Message::SetInterleave => {
+                match self.interleave_input.parse::<u8>() {
+                    Ok(depth) if depth >= 1 => {
+                        self.interleave = depth;
+                        self.status = format!("Глубина перемежения установлена на {}", self.interleave);
+                    }
+                    _ => self.status = "Ошибка: глубина перемежения от 1 до 255".to_string(),
+                }
+                self.status_opacity = 1.0;
+                self.status_set_time = Some(Instant::now());
+            }
+            Message::SetOverhead => {
+                if let Ok(overhead) = self.overhead_input.parse::<u8>() {
+                    self.overhead = overhead;
+                    self.status = format!("Избыточность RaptorQ установлена на {}%", self.overhead);
+                } else {
+                    self.status = "Ошибка: некорректная избыточность".to_string();
+                }
+                self.status_opacity = 1.0;
+                self.status_set_time = Some(Instant::now());
+            }
+            Message::SetRepeat => {
+                match self.repeat_input.parse::<u8>() {
+                    Ok(repeat) if repeat >= 1 => {
+                        self.repeat = repeat;
+                        self.status = format!("Число повторов установлено на {}", self.repeat);
+                    }
+                    _ => self.status = "Ошибка: число повторов от 1 до 255".to_string(),
+                }
+                self.status_opacity = 1.0;
+                self.status_set_time = Some(Instant::now());
+            }
+            Message::FecToggled(raptorq) => self.raptorq = raptorq,
             Message::PortChanged(value) => self.port_input = value,
             Message::BaudChanged(value) => self.baud_input = value,
             Message::RsChanged(value) => self.rs_input = value,
+            Message::InterleaveChanged(value) => self.interleave_input = value,
+            Message::OverheadChanged(value) => self.overhead_input = value,
+            Message::RepeatChanged(value) => self.repeat_input = value,
             Message::SelectFile => {
                 if let Some(file) = FileDialog::new().pick_file() {
                     self.file_path = Some(file.to_string_lossy().to_string());
@@ -141,12 +220,12 @@ fn new(flags: Self::Flags) -> (Self, Command<Message>) {
         println!("Начинаю отправку файла: {}", file_path);
         let port = self.port.clone();
         let baud = self.baud;
-        let rs = self.rs;
+        let options = self.send_options();
         let file_path = file_path.clone();
         return Command::perform(
             async move {
                 println!("Асинхронная задача запущена");
-                let result = super::core::send_file(&port, baud, rs, &file_path);
+                let result = diode_core::send_file(&port, baud, &options, &file_path).map_err(Arc::new);
                 println!("Асинхронная задача завершена с результатом: {:?}", result);
                 result
             },
@@ -195,8 +274,8 @@ fn new(flags: Self::Flags) -> (Self, Command<Message>) {
         }
     }
 
-    fn view(&self) -> Element<Message> {
-        use iced::widget::{button, column, row, text, text_input, container};
+    fn view(&self) -> Element<'_, Message> {
+        use iced::widget::{button, checkbox, column, row, text, text_input, container};
 
         column![
             row![
@@ -216,6 +295,24 @@ fn new(flags: Self::Flags) -> (Self, Command<Message>) {
                 button("OK").on_press(Message::SetRs),
                 text("(максимум 254)")
             ].spacing(5),
+            row![
+                text("Перемежение (блоки):"),
+                text_input("", &self.interleave_input).on_input(Message::InterleaveChanged),
+                button("OK").on_press(Message::SetInterleave),
+                text("(от 1 до 255)")
+            ].spacing(5),
+            row![
+                checkbox("Фонтанный код RaptorQ", self.raptorq, Message::FecToggled),
+                text("Избыточность (%):"),
+                text_input("", &self.overhead_input).on_input(Message::OverheadChanged),
+                button("OK").on_press(Message::SetOverhead),
+            ].spacing(5),
+            row![
+                text("Повторов:"),
+                text_input("", &self.repeat_input).on_input(Message::RepeatChanged),
+                button("OK").on_press(Message::SetRepeat),
+                text("(от 1 до 255)")
+            ].spacing(5),
             text(
                 self.file_path
                     .as_ref()
@@ -0,0 +1,209 @@
+
+
+use clap::{Parser, Subcommand, ValueEnum};
+
+mod gui;
+
+#[derive(Parser)]
+#[command(about = "File sender with CLI support")]
+struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
+    /// Последовательный порт (`ИМЯ?parity=even` — с чётностью), `file:ПУТЬ`, `udp://ХОСТ:ПОРТ`, `rfc2217://ХОСТ:ПОРТ` (чётность так же) или `-` для stdout
+    #[arg(long, global = true)]
+    port: Option<String>,
+    #[arg(long, global = true)]
+    baud: Option<u32>,
+    #[arg(long)]
+    rs: Option<u8>,
+    #[arg(long, global = true)]
+    interleave: Option<u8>,
+    /// Схема коррекции ошибок: блоки Reed-Solomon или фонтанный код RaptorQ
+    #[arg(long, value_enum)]
+    fec: Option<Fec>,
+    /// Избыточность RaptorQ в процентах
+    #[arg(long, global = true)]
+    overhead: Option<u8>,
+    /// Сколько раз отправить файл целиком
+    #[arg(long, global = true)]
+    repeat: Option<u8>,
+    #[arg(long, global = true)]
+    file: Option<String>,
+    /// Файл контрольной точки: отправка записывает в него, докуда дошла, а `resume` продолжает с этого места
+    #[arg(long, global = true)]
+    checkpoint: Option<std::path::PathBuf>,
+    #[arg(long)]
+    send: bool,
+    #[arg(long)]
+    nogui: bool,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Шлёт известную псевдослучайную последовательность для проверки линии приёмником
+    Selftest {
+        /// Сколько секунд слать на каждой скорости
+        #[arg(long, default_value_t = 10)]
+        duration: u64,
+        /// Перебрать скорости по очереди, например `921600,1500000,3000000`; приёмник начинает с первой
+        #[arg(long, value_delimiter = ',')]
+        sweep: Vec<u32>,
+    },
+    /// Оценивает время и надёжность передачи для разных схем коррекции и советует самую быструю из надёжных
+    Plan {
+        /// Размер файла в байтах; с `--file` берётся размер файла
+        #[arg(long)]
+        size: Option<u64>,
+        /// Вероятность ошибки бита в линии, например измеренная `selftest`
+        #[arg(long, default_value_t = 1e-6)]
+        ber: f64,
+        /// Бит в линии на байт: 10 для 8N1, 11 с битом чётности
+        #[arg(long, default_value_t = 10)]
+        bits_per_byte: u32,
+        /// Допустимая вероятность не принять файл
+        #[arg(long, default_value_t = 1e-6)]
+        target: f64,
+    },
+    /// Отправляет заново только части `--file`, которых не хватило приёмнику, по его коду пропусков
+    Resend {
+        /// Код пропусков, который напечатал приёмник (`DDGAP-…`)
+        #[arg(long)]
+        gap: String,
+    },
+    /// Продолжает прерванную отправку с `--checkpoint` под тем же идентификатором передачи
+    Resume,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Fec {
+    Rs,
+    Raptorq,
+}
+
+impl Args {
+    fn fec_mode(&self) -> diode_core::FecMode {
+        match self.fec {
+            Some(Fec::Raptorq) => diode_core::FecMode::RaptorQ { repair_percent: self.overhead.unwrap_or(diode_core::DEFAULT_REPAIR_PERCENT) },
+            Some(Fec::Rs) | None => diode_core::FecMode::ReedSolomon,
+        }
+    }
+
+    fn send_options(&self) -> diode_core::SendOptions {
+        let defaults = diode_core::SendOptions::default();
+        diode_core::SendOptions {
+            rs_bytes: self.rs.unwrap_or(defaults.rs_bytes),
+            interleave: self.interleave.unwrap_or(defaults.interleave),
+            fec: self.fec_mode(),
+            repeat: self.repeat.unwrap_or(defaults.repeat),
+            checkpoint: self.checkpoint.clone(),
+        }
+    }
+}
+
+fn selftest(port: &str, baud: u32, duration: u64, sweep: Vec<u32>) -> diode_core::Result<()> {
+    let plan = diode_core::SelfTestPlan {
+        bauds: if sweep.is_empty() { vec![baud] } else { sweep },
+        step: std::time::Duration::from_secs(duration),
+    };
+    let mut tx = diode_core::Endpoint::parse(port)?.open_tx(plan.bauds[0])?;
+    diode_core::send_selftest(&mut *tx, &plan)
+}
+
+fn plan(args: &Args, size: Option<u64>, ber: f64, bits_per_byte: u32, target: f64) -> diode_core::Result<()> {
+    let file_size = match (&args.file, size) {
+        (Some(path), _) => std::fs::metadata(path).map_err(diode_core::Error::io("Не удалось открыть файл"))?.len(),
+        (None, Some(size)) => size,
+        (None, None) => return Err(diode_core::Error::Policy("Укажите --file или --size".to_string())),
+    };
+    let link = diode_core::Link { baud: args.baud.unwrap_or(921600), bits_per_byte, ber };
+    let plan = diode_core::plan(file_size, &link, args.interleave.unwrap_or(1), target)?;
+    println!("Файл {} байт, скорость {}, BER {:e}, цель {:e}", file_size, link.baud, ber, target);
+    for estimate in &plan.estimates {
+        println!("{}", estimate);
+    }
+    match plan.recommended.map(|index| &plan.estimates[index]) {
+        Some(estimate) => {
+            let options = &estimate.options;
+            let flags = match options.fec {
+                diode_core::FecMode::ReedSolomon => format!("--rs {} --interleave {}", options.rs_bytes, options.interleave),
+                diode_core::FecMode::RaptorQ { repair_percent } => format!("--fec raptorq --overhead {}", repair_percent),
+            };
+            println!("Рекомендуется: {} --repeat {} ({})", flags, options.repeat, estimate);
+        }
+        None => println!("Ни одна схема не достигает цели: снизьте скорость или улучшите линию"),
+    }
+    Ok(())
+}
+
+fn resend(args: &Args, gap: &str) -> diode_core::Result<()> {
+    let gap = diode_core::GapReport::decode(gap)?;
+    let file_path = args.file.as_deref().ok_or_else(|| diode_core::Error::Policy("Укажите --file".to_string()))?;
+    let port = args.port.clone().unwrap_or_else(|| diode_core::find_com_port().unwrap_or("COM14".to_string()));
+    // Нарезку на части задаёт код пропусков: `--rs` и `--fec` здесь не действуют, а `--overhead` —
+    // только если части — сегменты RaptorQ.
+    let options = match gap.rs_bytes {
+        Some(rs_bytes) => diode_core::SendOptions { rs_bytes, fec: diode_core::FecMode::ReedSolomon, ..args.send_options() },
+        None => diode_core::SendOptions {
+            fec: diode_core::FecMode::RaptorQ { repair_percent: args.overhead.unwrap_or(diode_core::DEFAULT_REPAIR_PERCENT) },
+            ..args.send_options()
+        },
+    };
+    diode_core::resend_file(&port, args.baud.unwrap_or(921600), &options, file_path, &gap)
+}
+
+fn resume(args: &Args) -> diode_core::Result<()> {
+    let checkpoint = args.checkpoint.as_deref().ok_or_else(|| diode_core::Error::Policy("Укажите --checkpoint".to_string()))?;
+    let port = args.port.clone().unwrap_or_else(|| diode_core::find_com_port().unwrap_or("COM14".to_string()));
+    diode_core::resume_file(&port, args.baud.unwrap_or(921600), checkpoint)
+}
+
+fn main() {
+    let args = Args::parse();
+
+    if let Some(Command::Plan { size, ber, bits_per_byte, target }) = args.command {
+        if let Err(e) = plan(&args, size, ber, bits_per_byte, target) {
+            eprintln!("Ошибка планирования: {}", e);
+            std::process::exit(1);
+        }
+    } else if let Some(Command::Resend { gap }) = &args.command {
+        match resend(&args, gap) {
+            Ok(()) => eprintln!("Недостающие части отправлены"),
+            Err(e) => {
+                eprintln!("Ошибка повторной отправки: {}", e);
+                std::process::exit(1);
+            }
+        }
+    } else if let Some(Command::Resume) = &args.command {
+        match resume(&args) {
+            Ok(()) => eprintln!("Файл успешно отправлен"),
+            Err(e) => {
+                eprintln!("Ошибка продолжения отправки: {}", e);
+                std::process::exit(1);
+            }
+        }
+    } else if let Some(Command::Selftest { duration, sweep }) = args.command {
+        let port = args.port.unwrap_or_else(|| diode_core::find_com_port().unwrap_or("COM14".to_string()));
+        match selftest(&port, args.baud.unwrap_or(921600), duration, sweep) {
+            Ok(()) => eprintln!("Проверочный поток отправлен"),
+            Err(e) => {
+                eprintln!("Ошибка проверки линии: {}", e);
+                std::process::exit(1);
+            }
+        }
+    } else if args.nogui {
+        if args.send && let Some(file_path) = args.file.clone() {
+            let options = args.send_options();
+            let port = args.port.unwrap_or_else(|| diode_core::find_com_port().unwrap_or("COM14".to_string()));
+            let baud = args.baud.unwrap_or(921600);
+
+            match diode_core::send_file(&port, baud, &options, &file_path) {
+                Ok(_) => eprintln!("Файл успешно отправлен"),
+                Err(e) => eprintln!("Ошибка отправки файла: {}", e),
+            }
+        } else {
+            eprintln!("В режиме --nogui необходимо указать --send и --file");
+        }
+    } else {
+        gui::SenderApp::run_gui(args);
+    }
+}
\ No newline at end of file
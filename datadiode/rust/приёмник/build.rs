@@ -2,9 +2,12 @@ use std::env;
 use std::path::PathBuf;
 
 fn main() {
-    if env::var_os("CARGO_CFG_WINDOWS").is_some() {
+    let icon = PathBuf::from(env::var_os("CARGO_MANIFEST_DIR").unwrap()).join("resources/gnfgr5.ico");
+    println!("cargo:rerun-if-changed={}", icon.display());
+    // Иконка нужна только exe под Windows; без файла собираем без неё.
+    if env::var_os("CARGO_CFG_WINDOWS").is_some() && icon.exists() {
         let mut res = winresource::WindowsResource::new();
-        res.set_icon("E:/ruste/recieved/resources/gnfgr5.ico"); // Путь к файлу .ico
+        res.set_icon(icon.to_str().unwrap());
         res.compile().unwrap();
     }
-}
\ No newline at end of file
+}
@@ -1,49 +1,60 @@
 use iced::{Application, Settings, Element, Command, Subscription};
 use rfd::FileDialog;
 use super::Args;
-use super::core::receive_file;
+use diode_core::{Event, Receiver};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
 use std::time::Instant;
 
 #[derive(Default)]
 pub struct ReceiverApp {
     port: String,
     baud: u32,
-    rs: u8,
     output_dir: String,
     status: String,
     port_input: String,
     baud_input: String,
-    rs_input: String,
     status_opacity: f32,
     status_set_time: Option<Instant>,
     receiving: bool,
+    /// Сохранять файлы, не восстановленные целиком, с картой повреждений.
+    salvage: bool,
+    /// Тот же флажок для задачи опроса: она держит приёмник всё время чтения порта,
+    /// и окно не ждёт её, чтобы поменять настройку.
+    shared_salvage: Arc<AtomicBool>,
+    /// Итог коррекции последнего принятого файла: остаётся на экране, пока не придёт следующий.
+    last_stats: String,
+    /// Открытый порт живёт, пока идёт приём, и не переоткрывается между файлами. После остановки
+    /// он закрывается, только когда вернётся начатый опрос, и до этого новый приём не начать.
+    receiver: Option<Arc<Mutex<Receiver>>>,
 }
 
 impl ReceiverApp {
     pub fn run_gui(args: Args) {
-        let initial_port = args.port.unwrap_or_else(|| super::core::find_com_port().unwrap_or("COM16".to_string()));
+        let initial_port = args.port.unwrap_or_else(|| diode_core::find_com_port().unwrap_or("COM16".to_string()));
         let initial_baud = args.baud.unwrap_or(921600);
-        let initial_rs = args.rs.unwrap_or(10);
         let initial_dir = args.dir.unwrap_or_else(|| "received_files/".to_string());
 
         let settings = Settings {
             window: iced::window::Settings {
-                size: (650, 300),
+                size: (650, 400),
                 resizable: true,
                 ..iced::window::Settings::default()
             },
             flags: ReceiverApp {
                 port: initial_port,
                 baud: initial_baud,
-                rs: initial_rs,
                 output_dir: initial_dir,
                 status: String::new(),
                 port_input: String::new(),
                 baud_input: String::new(),
-                rs_input: String::new(),
                 status_opacity: 1.0,
                 status_set_time: None,
                 receiving: false,
+                salvage: args.salvage,
+                shared_salvage: Arc::new(AtomicBool::new(args.salvage)),
+                last_stats: String::new(),
+                receiver: None,
             },
             ..Settings::default()
         };
@@ -55,17 +66,36 @@ impl ReceiverApp {
 pub enum Message {
     SetPort,
     SetBaud,
-    SetRs,
     PortChanged(String),
     BaudChanged(String),
-    RsChanged(String),
     SelectDir,
     StartReceiving,
     StopReceiving,
-    FileReceived(Result<String, String>),
+    SalvageToggled(bool),
+    Polled(Result<Option<Event>, Arc<diode_core::Error>>),
     Tick,
 }
 
+impl ReceiverApp {
+    fn poll(&self) -> Command<Message> {
+        match &self.receiver {
+            Some(receiver) => {
+                let receiver = receiver.clone();
+                let salvage = self.shared_salvage.clone();
+                Command::perform(
+                    async move {
+                        let mut receiver = receiver.lock().unwrap();
+                        receiver.set_salvage(salvage.load(Ordering::Relaxed));
+                        receiver.poll().map_err(Arc::new)
+                    },
+                    Message::Polled,
+                )
+            }
+            None => Command::none(),
+        }
+    }
+}
+
 impl Application for ReceiverApp {
     type Executor = iced::executor::Default;
     type Message = Message;
@@ -98,23 +128,8 @@ impl Application for ReceiverApp {
                 self.status_opacity = 1.0;
                 self.status_set_time = Some(Instant::now());
             }
-            Message::SetRs => {
-                if let Ok(rs) = self.rs_input.parse::<u8>() {
-                    if rs <= 254 {
-                        self.rs = rs;
-                        self.status = format!("Reed-Solomon установлен на {} байт", self.rs);
-                    } else {
-                        self.status = "Ошибка: RS должен быть <= 254".to_string();
-                    }
-                } else {
-                    self.status = "Ошибка: некорректное значение RS".to_string();
-                }
-                self.status_opacity = 1.0;
-                self.status_set_time = Some(Instant::now());
-            }
             Message::PortChanged(value) => self.port_input = value,
             Message::BaudChanged(value) => self.baud_input = value,
-            Message::RsChanged(value) => self.rs_input = value,
             Message::SelectDir => {
                 if let Some(dir) = FileDialog::new().pick_folder() {
                     self.output_dir = dir.to_string_lossy().into_owned() + "/";
@@ -123,40 +138,64 @@ impl Application for ReceiverApp {
                     self.status_set_time = Some(Instant::now());
                 }
             }
+            // Прошлый приём ещё не отпустил порт.
+            Message::StartReceiving if self.receiver.is_some() => {}
             Message::StartReceiving => {
-                self.receiving = true;
-                self.status = "Слушаю порт...".to_string();
-                let port = self.port.clone();
-                let baud = self.baud;
-                let rs = self.rs;
-                let output_dir = self.output_dir.clone();
-                return Command::perform(
-                    async move { receive_file(&port, baud, rs, &output_dir) },
-                    Message::FileReceived,
-                );
+                match Receiver::open(&self.port, self.baud, &self.output_dir) {
+                    Ok(receiver) => {
+                        self.receiving = true;
+                        self.receiver = Some(Arc::new(Mutex::new(receiver)));
+                        self.status = "Слушаю порт...".to_string();
+                        return self.poll();
+                    }
+                    Err(e) => {
+                        self.status = format!("Ошибка приёма: {}", e);
+                        self.status_opacity = 1.0;
+                        self.status_set_time = Some(Instant::now());
+                    }
+                }
             }
             Message::StopReceiving => {
                 self.receiving = false;
                 self.status = "Приём остановлен".to_string();
             }
-            Message::FileReceived(result) => {
-                self.status = match result {
-                    Ok(filename) => format!("Файл получен: {}", filename),
-                    Err(e) if e.contains("Таймаут") => "Слушаю порт...".to_string(),
-                    Err(e) => format!("Ошибка приёма: {}", e),
+            Message::SalvageToggled(salvage) => {
+                self.salvage = salvage;
+                self.shared_salvage.store(salvage, Ordering::Relaxed);
+            }
+            // Результат опроса, начатого до остановки, тоже показывается: файл мог успеть сохраниться.
+            Message::Polled(result) => {
+                let status = match result {
+                    Ok(None) => None,
+                    Ok(Some(Event::Received(file))) => {
+                        match &file.damage {
+                            Some(damage) => {
+                                self.last_stats =
+                                    format!("Последний файл частичный, {}\nКод пропусков: {}\n{}", damage, damage.gap.encode(), file.stats);
+                                Some(format!("Файл сохранён частично: {}", file.path))
+                            }
+                            None => {
+                                self.last_stats = format!("Последний файл: {}", file.stats);
+                                Some(format!("Файл получен: {}\nSHA-256: {}", file.path, file.sha256))
+                            }
+                        }
+                    }
+                    Ok(Some(Event::Failed(e))) => Some(format!("Ошибка приёма: {}", e)),
+                    Ok(Some(Event::Suspended(message))) => Some(format!("Ожидание продолжения: {}", message)),
+                    Err(e) => {
+                        self.receiving = false;
+                        Some(format!("Ошибка приёма: {}", e))
+                    }
                 };
-                self.status_opacity = 1.0;
-                self.status_set_time = Some(Instant::now());
+                if let Some(status) = status {
+                    self.status = status;
+                    self.status_opacity = 1.0;
+                    self.status_set_time = Some(Instant::now());
+                }
                 if self.receiving {
-                    let port = self.port.clone();
-                    let baud = self.baud;
-                    let rs = self.rs;
-                    let output_dir = self.output_dir.clone();
-                    return Command::perform(
-                        async move { receive_file(&port, baud, rs, &output_dir) },
-                        Message::FileReceived,
-                    );
+                    return self.poll();
                 }
+                self.receiver = None;
             }
             Message::Tick => {
                 if let Some(set_time) = self.status_set_time {
@@ -182,8 +221,8 @@ impl Application for ReceiverApp {
         }
     }
 
-    fn view(&self) -> Element<Message> {
-        use iced::widget::{button, column, row, text, text_input, container};
+    fn view(&self) -> Element<'_, Message> {
+        use iced::widget::{button, checkbox, column, row, text, text_input, container};
 
         column![
             row![
@@ -197,20 +236,17 @@ impl Application for ReceiverApp {
                 button("OK").on_press(Message::SetBaud),
                 text("(максимум 3 млн)")
             ].spacing(5),
-            row![
-                text("Reed-Solomon (байты):"),
-                text_input("", &self.rs_input).on_input(Message::RsChanged),
-                button("OK").on_press(Message::SetRs),
-                text("(максимум 254)")
-            ].spacing(5),
             row![
                 text("Путь сохранения:"),
                 text(&self.output_dir),
                 button("Выбрать").on_press(Message::SelectDir),
             ].spacing(5),
+            checkbox("Сохранять повреждённые файлы частично", self.salvage, Message::SalvageToggled),
             container(
                 if self.receiving {
                     button("Прекратить прослушивание").on_press(Message::StopReceiving)
+                } else if self.receiver.is_some() {
+                    button("Остановка...")
                 } else {
                     button("Начать приём").on_press(Message::StartReceiving)
                 }
@@ -225,6 +261,7 @@ impl Application for ReceiverApp {
             } else {
                 text("")
             },
+            text(&self.last_stats).size(14),
         ]
         .spacing(10)
         .padding(20)
@@ -0,0 +1,110 @@
+#![windows_subsystem = "windows"]
+
+use clap::{Parser, Subcommand};
+
+mod gui;
+
+#[derive(Parser)]
+#[command(about = "File receiver with CLI support")]
+struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
+    /// Последовательный порт (`ИМЯ?parity=even` — с чётностью), `file:ПУТЬ`, `udp://ХОСТ:ПОРТ`, `rfc2217://ХОСТ:ПОРТ` (чётность так же) или `-` для stdin
+    #[arg(long, global = true)]
+    port: Option<String>,
+    #[arg(long, global = true)]
+    baud: Option<u32>,
+    /// Устарело: параметры Reed-Solomon приёмник берёт из заголовка передачи.
+    #[arg(long, hide = true)]
+    rs: Option<u8>,
+    #[arg(long)]
+    dir: Option<String>,
+    #[arg(long)]
+    start: bool,
+    /// Сохранять файл, не восстановленный целиком: с нулями на месте потерь и картой повреждений рядом
+    #[arg(long)]
+    salvage: bool,
+    #[arg(long)]
+    nogui: bool,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Принимает проверочный поток отправителя и считает ошибки бит, потери байтов и BER
+    Selftest,
+}
+
+fn selftest(port: &str, baud: u32) -> diode_core::Result<()> {
+    let rx = diode_core::Endpoint::parse(port)?.open_rx(baud)?;
+    let reports = diode_core::receive_selftest(rx, baud)?;
+    for report in &reports {
+        println!("{}", report);
+    }
+    if reports.len() > 1 {
+        match reports.iter().filter(|report| report.is_clean()).map(|report| report.baud).max() {
+            Some(baud) => println!("Наибольшая скорость без ошибок: {}", baud),
+            None => println!("Ни одна скорость не прошла без ошибок"),
+        }
+    }
+    Ok(())
+}
+
+#[tokio::main]
+async fn main() {
+    let args = Args::parse();
+
+    if let Some(Command::Selftest) = args.command {
+        let port = args.port.unwrap_or_else(|| diode_core::find_com_port().unwrap_or("COM16".to_string()));
+        if let Err(e) = selftest(&port, args.baud.unwrap_or(921600)) {
+            eprintln!("Ошибка проверки линии: {}", e);
+            std::process::exit(1);
+        }
+    } else if args.nogui {
+        if args.start {
+            let port = args.port.unwrap_or_else(|| diode_core::find_com_port().unwrap_or("COM16".to_string()));
+            let baud = args.baud.unwrap_or(921600);
+            let output_dir = args.dir.unwrap_or_else(|| "received_files/".to_string());
+
+            loop {
+                let mut receiver = match diode_core::Receiver::open(&port, baud, &output_dir) {
+                    Ok(mut receiver) => {
+                        receiver.set_salvage(args.salvage);
+                        receiver
+                    }
+                    Err(e) => {
+                        eprintln!("{}", e);
+                        tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+                        continue;
+                    }
+                };
+                loop {
+                    match receiver.poll() {
+                        Ok(Some(diode_core::Event::Received(file))) => {
+                            match &file.damage {
+                                Some(damage) => {
+                                    println!("Файл сохранён частично: {} ({})", file.path, damage);
+                                    println!("Код пропусков для sender resend --gap: {}", damage.gap.encode());
+                                }
+                                None => println!("Файл получен: {} (SHA-256 {})", file.path, file.sha256),
+                            }
+                            println!("Коррекция: {}", file.stats);
+                        }
+                        Ok(Some(diode_core::Event::Failed(e))) => eprintln!("Ошибка приёма: {}", e),
+                        Ok(Some(diode_core::Event::Suspended(message))) => eprintln!("Ожидание продолжения: {}", message),
+                        Ok(None) => {}
+                        Err(diode_core::Error::Closed) => return,
+                        Err(e) => {
+                            eprintln!("{}", e);
+                            break;
+                        }
+                    }
+                }
+                tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+            }
+        } else {
+            eprintln!("В режиме --nogui необходимо указать --start");
+        }
+    } else {
+        gui::ReceiverApp::run_gui(args);
+    }
+}
\ No newline at end of file